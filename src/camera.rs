@@ -0,0 +1,174 @@
+use macroquad::prelude::*;
+
+// window pixels per cell at zoom 1, the board is fit in the window within these bounds
+const MIN_CELL_SIZE: f32 = 16.;
+const MAX_CELL_SIZE: f32 = 64.;
+const MIN_ZOOM: f32 = 0.25;
+const MAX_ZOOM: f32 = 4.;
+const WHEEL_ZOOM_STEP: f32 = 1.1;
+// window pixels per second
+const KEYBOARD_SCROLL_SPEED: f32 = 600.;
+
+// how far the view can be panned
+#[derive(Copy, Clone, PartialEq)]
+pub enum CameraBounds {
+    // the board is kept in the window
+    Board,
+    // torus board, panning never stops at an edge
+    Wrap,
+    // endless board, the board size only sets the starting cell size
+    Unbounded,
+}
+
+// maps board units (a cell is 1x1) to window pixels and back
+// everything is computed from the current window size, so drawing and
+// input picking always agree, even right after a resize
+pub struct BoardCamera {
+    board_size: Vec2,
+    // board point shown at the top left corner of the window
+    target: Vec2,
+    zoom: f32,
+    // mouse position of the previous frame while dragging
+    drag_pos: Option<Vec2>,
+    bounds: CameraBounds,
+}
+
+impl BoardCamera {
+    pub fn new(board_size: Vec2, bounds: CameraBounds) -> Self {
+        BoardCamera {
+            board_size,
+            target: Vec2::ZERO,
+            zoom: 1.,
+            drag_pos: None,
+            bounds,
+        }
+    }
+
+    fn cell_size(&self) -> f32 {
+        f32::min(
+            screen_width() / self.board_size.x,
+            screen_height() / self.board_size.y,
+        )
+        .clamp(MIN_CELL_SIZE, MAX_CELL_SIZE)
+    }
+
+    // window pixels per board unit
+    pub fn scale(&self) -> f32 {
+        self.cell_size() * self.zoom
+    }
+
+    pub fn to_screen(&self, board_pos: Vec2) -> Vec2 {
+        (board_pos - self.target()) * self.scale()
+    }
+
+    pub fn to_board(&self, screen_pos: Vec2) -> Vec2 {
+        screen_pos / self.scale() + self.target()
+    }
+
+    // part of the board visible in the window
+    pub fn view_rect(&self) -> Rect {
+        let target = self.target();
+        let scale = self.scale();
+        Rect::new(
+            target.x,
+            target.y,
+            screen_width() / scale,
+            screen_height() / scale,
+        )
+    }
+
+    // a board that grew, the view stays where it is
+    pub fn set_board_size(&mut self, board_size: Vec2) {
+        self.board_size = board_size;
+    }
+
+    pub fn look_at(&mut self, board_pos: Vec2) {
+        self.target = board_pos - self.view_rect().size() / 2.;
+        self.target = self.target();
+    }
+
+    // scrolls just enough to get the board rect into the window
+    pub fn show(&mut self, rect: Rect) {
+        let view = self.view_rect();
+        self.target = vec2(
+            view.x.min(rect.x).max(rect.right() - view.w),
+            view.y.min(rect.y).max(rect.bottom() - view.h),
+        );
+        self.target = self.target();
+    }
+
+    // wheel and +/- zoom, middle button drag and WASD pan
+    pub fn process_input(&mut self) {
+        let mouse = Vec2::from(mouse_position());
+
+        // signum of 0 is 1, a wheel that doesn't move is no step
+        let wheel = mouse_wheel().1;
+        let mut zoom_steps = if wheel == 0. { 0. } else { wheel.signum() };
+        if is_key_pressed(KeyCode::Equal) || is_key_pressed(KeyCode::KpAdd) {
+            zoom_steps += 1.;
+        }
+        if is_key_pressed(KeyCode::Minus) || is_key_pressed(KeyCode::KpSubtract) {
+            zoom_steps -= 1.;
+        }
+        if zoom_steps != 0. {
+            // keep the board point under the mouse in place
+            let anchor = self.to_board(mouse);
+            self.zoom = (self.zoom * WHEEL_ZOOM_STEP.powf(zoom_steps)).clamp(MIN_ZOOM, MAX_ZOOM);
+            self.target = anchor - mouse / self.scale();
+        }
+
+        if is_mouse_button_down(MouseButton::Middle) {
+            if let Some(prev) = self.drag_pos {
+                self.target -= (mouse - prev) / self.scale();
+            }
+            self.drag_pos = Some(mouse);
+        } else {
+            self.drag_pos = None;
+        }
+
+        let mut scroll = Vec2::ZERO;
+        if is_key_down(KeyCode::A) {
+            scroll.x -= 1.;
+        }
+        if is_key_down(KeyCode::D) {
+            scroll.x += 1.;
+        }
+        if is_key_down(KeyCode::W) {
+            scroll.y -= 1.;
+        }
+        if is_key_down(KeyCode::S) {
+            scroll.y += 1.;
+        }
+        self.target += scroll * KEYBOARD_SCROLL_SPEED * get_frame_time() / self.scale();
+
+        self.target = self.target();
+    }
+
+    // the stored target kept on the board, centered along the axes the board fits in
+    // a torus target is only brought back into the first copy of the board
+    fn target(&self) -> Vec2 {
+        match self.bounds {
+            CameraBounds::Board => {}
+            CameraBounds::Wrap => {
+                return vec2(
+                    self.target.x.rem_euclid(self.board_size.x),
+                    self.target.y.rem_euclid(self.board_size.y),
+                )
+            }
+            CameraBounds::Unbounded => return self.target,
+        }
+        let scale = self.scale();
+        let view = vec2(screen_width() / scale, screen_height() / scale);
+        let clamp = |target: f32, board: f32, view: f32| {
+            if board <= view {
+                (board - view) / 2.
+            } else {
+                target.clamp(0., board - view)
+            }
+        };
+        vec2(
+            clamp(self.target.x, self.board_size.x, view.x),
+            clamp(self.target.y, self.board_size.y, view.y),
+        )
+    }
+}
@@ -1,7 +1,14 @@
+use crate::camera::*;
 use crate::common::*;
+use crate::daily::*;
+use crate::keys::*;
+use crate::messges::*;
+use crate::replay::*;
+use crate::stats::*;
+use crate::theme::*;
 use macroquad::prelude::*;
-use miniquad::window::set_window_size;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 // enum GameState {
 //     InGame {
@@ -10,35 +17,616 @@ use std::collections::HashSet;
 //     GameOver,
 // }
 
+struct GameResult {
+    won: bool,
+    elapsed: Duration,
+    stats: BoardStats,
+    cleared: usize,
+}
+
 pub struct Client {
-    field: Box<dyn TClientField>, // game_state: GameState,
-                                  // client_id
+    field: Box<dyn TClientField>,
+    // the loaded theme and the one drawn with, after the accessibility options
+    base_theme: Theme,
+    theme: Theme,
+    accessibility: Accessibility,
+    keys: KeyBindings,
+    question_marks: bool,
+    init_params: InitParams,
+    recorder: Option<Recorder>,
+    // opens and flags sent during the current game
+    clicks: usize,
+    // see InitParams::lives
+    lives_left: usize,
+    // the last status of a time attack or a survival game
+    mode_status: Option<ModeStatus>,
+    // the day and the player name of a daily challenge
+    daily: Option<(Date, String)>,
+    game_result: Option<GameResult>,
+    // loaded while the statistics screen is open
+    player_stats: Option<PlayerStats>,
+    // message shown at the bottom of the window and the time it was set
+    status: Option<(String, f64)>,
+    // game_state: GameState,
+    // client_id
+}
+
+pub const AUTOSAVE_PATH: &str = "rs-minesweeper.autosave";
+const QUICKSAVE_PATH: &str = "rs-minesweeper.save";
+const STATS_PATH: &str = "rs-minesweeper.stats";
+pub const DAILY_PATH: &str = "rs-minesweeper.daily";
+const STATS_HISTOGRAM_BUCKETS: usize = 10;
+const STATUS_SHOW_TIME: f64 = 3.;
+
+const REPLAY_MAX_SPEED: f32 = 16.;
+const REPLAY_MIN_SPEED: f32 = 1. / 16.;
+
+fn new_field(init_params: &InitParams) -> Box<dyn TClientField> {
+    match init_params.grid_type.topology(&init_params.neighbourhood) {
+        Some(topology) => Box::new(GridClientField::new(init_params, topology)),
+        None => Box::new(EndlessClientField::new(&init_params.neighbourhood)),
+    }
 }
 
 impl Client {
     pub fn new(init_params: &InitParams) -> Client {
-        match init_params.grid_type {
-            GridType::RectGrid { heigth, width } => Client {
-                field: Box::new(RectClientField::new(heigth, width, init_params.mines_cnt)),
-            },
-            GridType::HexGrid => std::unimplemented!(),
+        Client {
+            field: new_field(init_params),
+            base_theme: Theme::default(),
+            theme: Theme::default(),
+            accessibility: Accessibility::default(),
+            keys: KeyBindings::default(),
+            question_marks: false,
+            init_params: init_params.clone(),
+            recorder: None,
+            clicks: 0,
+            lives_left: init_params.lives,
+            mode_status: None,
+            daily: None,
+            game_result: None,
+            player_stats: None,
+            status: None,
         }
     }
 
-    pub async fn run<F>(&mut self, mut process_client_data: F) -> !
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.base_theme = theme;
+        self.theme = self.base_theme.with_accessibility(&self.accessibility);
+    }
+
+    pub fn set_accessibility(&mut self, accessibility: Accessibility) {
+        self.accessibility = accessibility;
+        self.theme = self.base_theme.with_accessibility(&self.accessibility);
+    }
+
+    // F6 - next palette, F7 - glyphs, F8 - large text
+    fn process_accessibility_input(&mut self) {
+        let mut accessibility = self.accessibility.clone();
+        let status = if is_key_pressed(KeyCode::F6) {
+            accessibility.palette = accessibility.palette.next();
+            format!("palette: {}", accessibility.palette.name())
+        } else if is_key_pressed(KeyCode::F7) {
+            accessibility.glyphs = !accessibility.glyphs;
+            format!(
+                "glyphs: {}",
+                if accessibility.glyphs { "on" } else { "off" }
+            )
+        } else if is_key_pressed(KeyCode::F8) {
+            accessibility.large_text = !accessibility.large_text;
+            format!(
+                "large text: {}",
+                if accessibility.large_text {
+                    "on"
+                } else {
+                    "off"
+                }
+            )
+        } else {
+            return;
+        };
+        self.set_accessibility(accessibility);
+        self.set_status(status);
+    }
+
+    pub fn set_key_bindings(&mut self, keys: KeyBindings) {
+        self.keys = keys;
+    }
+
+    pub fn set_question_marks(&mut self, enabled: bool) {
+        self.question_marks = enabled;
+        self.field.set_question_marks(enabled);
+    }
+
+    pub fn set_daily(&mut self, date: Date, player: String) {
+        self.daily = Some((date, player));
+    }
+
+    pub fn record_to(&mut self, recorder: Recorder) {
+        self.recorder = Some(recorder);
+    }
+
+    // returns when the window is being closed
+    // F5 - quick save, F9 - quick load, tab - statistics
+    // F6, F7, F8 - accessibility options
+    // process_client_data gets None every frame to collect the messages the server pushes
+    pub async fn run<F>(&mut self, mut process_client_data: F)
     where
-        F: FnMut(Vec<Coords>) -> Vec<Cell>,
+        F: FnMut(Option<ClientMessage>) -> Vec<ServerMessage>,
     {
-        self.field.draw();
+        prevent_quit();
+        self.field.draw(&self.theme);
         loop {
-            if let Some(coords) = self.field.process_input() {
-                let opened_cells = process_client_data(coords);
-                self.field.update(opened_cells);
+            if is_quit_requested() {
+                return;
+            }
+            if is_key_pressed(KeyCode::Tab) {
+                self.toggle_player_stats();
+            }
+            self.process_accessibility_input();
+            let request = if self.player_stats.is_some() {
+                None
+            } else if is_key_pressed(KeyCode::F5) {
+                Some(ClientMessage::SaveGame(QUICKSAVE_PATH.into()))
+            } else if is_key_pressed(KeyCode::F9) {
+                Some(ClientMessage::LoadGame(QUICKSAVE_PATH.into()))
+            } else if self.init_params.practice
+                && self.keys.undo.iter().any(|&key| is_key_pressed(key))
+            {
+                Some(ClientMessage::Undo)
+            } else {
+                self.field.process_input(&self.keys)
+            };
+            if let Some(request) = request {
+                let done_status = match request {
+                    ClientMessage::SaveGame(_) => Some("game saved"),
+                    ClientMessage::LoadGame(_) => Some("game loaded"),
+                    ClientMessage::Undo | ClientMessage::ShowChunks(_) => None,
+                    _ => {
+                        if self.game_result.is_none() {
+                            self.clicks += 1;
+                        }
+                        None
+                    }
+                };
+                self.record(|rec| rec.record_request(&request));
+                let response = process_client_data(Some(request));
+                self.record(|rec| rec.record_response(&response));
+                if let Some(status) = done_status {
+                    self.set_status(status.to_string());
+                }
+                self.process_server_data(response);
+            }
+            let pushed = process_client_data(None);
+            if !pushed.is_empty() {
+                self.record(|rec| rec.record_response(&pushed));
+                self.process_server_data(pushed);
+            }
+            self.field.draw(&self.theme);
+            self.draw_hud();
+            self.draw_game_result();
+            if let Some(stats) = &self.player_stats {
+                self.draw_player_stats(stats);
             }
-            self.field.draw();
+            self.draw_status();
             next_frame().await;
         }
     }
+
+    pub fn process_server_data(&mut self, server_package: Vec<ServerMessage>) {
+        for msg in server_package {
+            match msg {
+                ServerMessage::NewGame(init_params) => {
+                    // a board of the same shape, the next one of a time attack, keeps the window
+                    // and the view of the last one
+                    if (&init_params.grid_type, &init_params.neighbourhood)
+                        == (&self.init_params.grid_type, &self.init_params.neighbourhood)
+                        && init_params.multi_mines == self.init_params.multi_mines
+                    {
+                        self.field.reset();
+                    } else {
+                        self.field = new_field(&init_params);
+                        self.field.set_question_marks(self.question_marks);
+                    }
+                    self.lives_left = init_params.lives;
+                    self.init_params = init_params;
+                    self.clicks = 0;
+                    self.mode_status = None;
+                    self.game_result = None;
+                }
+                // the cells and flags of the grown board come next,
+                // the clicks are kept as it's the same game
+                ServerMessage::Grown(init_params) => {
+                    self.field.add_row();
+                    self.init_params = init_params;
+                }
+                // the cells and flags left after the undo come next
+                ServerMessage::Undone => {
+                    self.field.reset();
+                    self.game_result = None;
+                }
+                ServerMessage::Cells(cells) => self.field.update(cells),
+                ServerMessage::Marked(flags) => {
+                    for (crds, flag) in flags {
+                        self.field.set_marked(crds, flag);
+                    }
+                }
+                ServerMessage::GameOver {
+                    won,
+                    elapsed,
+                    stats,
+                    mines,
+                    cleared,
+                } => {
+                    self.field.reveal_mines(mines, won);
+                    let res = GameResult {
+                        won,
+                        elapsed,
+                        stats,
+                        cleared,
+                    };
+                    // a game loaded already finished has no clicks of this player,
+                    // endless and mode games have a score rather than a time and are kept off the records
+                    let scored = matches!(self.init_params.grid_type, GridType::EndlessGrid)
+                        || self.init_params.mode != GameMode::Classic;
+                    if self.game_result.is_none() && self.clicks > 0 && !scored {
+                        self.save_game_record(&res);
+                        if self.daily_date().is_some() && !self.init_params.practice {
+                            self.save_daily_result(&res);
+                        }
+                    }
+                    self.game_result = Some(res);
+                }
+                ServerMessage::Lives(lives_left) => self.lives_left = lives_left,
+                ServerMessage::Mode(status) => self.mode_status = Some(status),
+                ServerMessage::Error(e) => self.set_status(e),
+            }
+        }
+    }
+
+    fn save_game_record(&mut self, res: &GameResult) {
+        let record = GameRecord {
+            finished_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |x| x.as_secs()),
+            preset: self.init_params.preset_name(),
+            seed: self.init_params.seed,
+            time: res.elapsed,
+            won: res.won,
+            bbbv: res.stats.bbbv,
+            clicks: self.clicks,
+            // todo: count hints once there are any
+            hints: 0,
+            practice: self.init_params.practice,
+        };
+        if let Err(e) = PlayerStats::append(STATS_PATH, &record) {
+            self.set_status(format!("can't save game record: {}", e));
+        }
+    }
+
+    // the day of the daily board being played, a game loaded from a file isn't one
+    fn daily_date(&self) -> Option<Date> {
+        let (date, _) = self.daily.as_ref()?;
+        let seed = daily_seed(*date, &self.init_params.preset_name());
+        (self.init_params.seed == Some(seed)).then_some(*date)
+    }
+
+    fn save_daily_result(&mut self, res: &GameResult) {
+        let (date, player) = self.daily.clone().unwrap();
+        let result = DailyResult {
+            date,
+            preset: self.init_params.preset_name(),
+            player,
+            time: res.elapsed,
+            won: res.won,
+            bbbv: res.stats.bbbv,
+            clicks: self.clicks,
+        };
+        match DailyResults::append(DAILY_PATH, &result) {
+            Ok(true) => self.set_status("daily result saved".to_string()),
+            Ok(false) => self.set_status("only the first daily game counts".to_string()),
+            Err(e) => self.set_status(format!("can't save daily result: {}", e)),
+        }
+    }
+
+    fn toggle_player_stats(&mut self) {
+        if self.player_stats.take().is_none() {
+            match PlayerStats::load(STATS_PATH) {
+                Ok(stats) => self.player_stats = Some(stats),
+                Err(e) => self.set_status(format!("can't load statistics: {}", e)),
+            }
+        }
+    }
+
+    fn draw_player_stats(&self, stats: &PlayerStats) {
+        draw_rectangle(
+            0.,
+            0.,
+            screen_width(),
+            screen_height(),
+            Color::new(0., 0., 0., 0.85),
+        );
+        let font_size = 20. * self.theme.text_scale;
+        let line_heigth = font_size * 1.2;
+        let mut y = line_heigth;
+        let mut line = |text: &str| {
+            draw_text(text, 10., y, font_size, WHITE);
+            y += line_heigth;
+        };
+
+        let (games, wins) = (stats.games_cnt(), stats.wins_cnt());
+        let (streak, best_streak) = stats.streaks();
+        line("Statistics (tab to close)");
+        line(&format!(
+            "games: {}  won: {} ({:.0}%)",
+            games,
+            wins,
+            100. * wins as f32 / usize::max(games, 1) as f32
+        ));
+        line(&format!("win streak: {}  best: {}", streak, best_streak));
+        line("best times:");
+        for (preset, time) in stats.best_times() {
+            line(&format!("  {}: {:.2}s", preset, time.as_secs_f32()));
+        }
+
+        let preset = self.init_params.preset_name();
+        line(&format!("{} times:", preset));
+        let hist = match stats.time_histogram(&preset, STATS_HISTOGRAM_BUCKETS) {
+            Some(hist) => hist,
+            None => {
+                line("  no games won yet");
+                return;
+            }
+        };
+        let hist_heigth = 80.;
+        let bar_width = (screen_width() - 20.) / hist.buckets.len() as f32;
+        let max_cnt = *hist.buckets.iter().max().unwrap_or(&1) as f32;
+        for (i, cnt) in hist.buckets.iter().enumerate() {
+            let h = hist_heigth * *cnt as f32 / max_cnt;
+            draw_rectangle(
+                10. + i as f32 * bar_width,
+                y + hist_heigth - h,
+                bar_width - 2.,
+                h,
+                GOLD,
+            );
+        }
+        y += hist_heigth + line_heigth;
+        draw_text(
+            format!("{:.2}s", hist.min.as_secs_f32()).as_str(),
+            10.,
+            y,
+            font_size,
+            WHITE,
+        );
+        let max = format!("{:.2}s", hist.max.as_secs_f32());
+        let max_width = measure_text(&max, None, font_size as u16, 1.).width;
+        draw_text(&max, screen_width() - 10. - max_width, y, font_size, WHITE);
+    }
+
+    fn draw_game_result(&self) {
+        let res = match &self.game_result {
+            Some(res) => res,
+            None => return,
+        };
+        let secs = res.elapsed.as_secs_f32();
+        let bbbv = res.stats.bbbv;
+        let efficiency = if self.clicks == 0 {
+            "-".to_string()
+        } else {
+            format!("{:.0}%", 100. * bbbv as f32 / self.clicks as f32)
+        };
+        let title = format!(
+            "{}{}",
+            if res.won { "Cleared!" } else { "Boom!" },
+            if self.init_params.practice {
+                " (practice)"
+            } else {
+                ""
+            }
+        );
+        let title = match self.daily_date() {
+            Some(date) => format!("{} daily {}", title, date),
+            None => title,
+        };
+        let lines = match (self.init_params.mode, &self.mode_status) {
+            (
+                GameMode::TimeAttack { limit },
+                Some(ModeStatus::TimeAttack { boards_cleared, .. }),
+            ) => vec![
+                "Time's up!".to_string(),
+                format!("time: {:.0}s", limit.as_secs_f32()),
+                format!("score: {} boards cleared", boards_cleared),
+            ],
+            (GameMode::Survival { .. }, Some(ModeStatus::Survival { rows_added, .. })) => vec![
+                title,
+                format!("survived: {:.2}s", secs),
+                format!("score: {} rows added", rows_added),
+            ],
+            _ if matches!(self.init_params.grid_type, GridType::EndlessGrid) => vec![
+                title,
+                format!("time: {:.2}s", secs),
+                format!("score: {} cells cleared", res.cleared),
+            ],
+            _ => vec![
+                title,
+                format!("time: {:.2}s", secs),
+                format!("3BV: {}  3BV/s: {:.2}", bbbv, bbbv as f32 / secs.max(0.001)),
+                format!("clicks: {}  efficiency: {}", self.clicks, efficiency),
+                format!(
+                    "openings: {}  islands: {}",
+                    res.stats.openings, res.stats.islands
+                ),
+                format!("zero cells: {:.1}%", res.stats.zero_cells_percent),
+            ],
+        };
+
+        let font_size = 20. * self.theme.text_scale;
+        let line_heigth = font_size * 1.2;
+        let text_width = lines
+            .iter()
+            .map(|line| measure_text(line, None, font_size as u16, 1.).width)
+            .fold(0., f32::max);
+        let (w, h) = (text_width + 20., line_heigth * lines.len() as f32 + 10.);
+        let (x, y) = ((screen_width() - w) / 2., (screen_height() - h) / 2.);
+        draw_rectangle(x, y, w, h, Color::new(0., 0., 0., 0.75));
+        for (i, line) in lines.iter().enumerate() {
+            draw_text(
+                line,
+                x + 10.,
+                y + 5. + line_heigth * (i as f32 + 0.8),
+                font_size,
+                WHITE,
+            );
+        }
+    }
+
+    // in the top right corner, the lives of games of more than one life
+    // and the status of a time attack or a survival game
+    fn draw_hud(&self) {
+        let mut lines = Vec::new();
+        if self.init_params.lives > 1 {
+            lines.push(format!(
+                "lives: {}/{}",
+                self.lives_left, self.init_params.lives
+            ));
+        }
+        match &self.mode_status {
+            Some(ModeStatus::TimeAttack {
+                boards_cleared,
+                time_left,
+            }) => lines.push(format!(
+                "boards: {}  left: {}s",
+                boards_cleared,
+                time_left.as_secs()
+            )),
+            Some(ModeStatus::Survival {
+                rows_added,
+                next_row_in,
+            }) => lines.push(format!(
+                "rows: {}  next in: {}s",
+                rows_added,
+                next_row_in.as_secs()
+            )),
+            None => {}
+        }
+        let font_size = 24. * self.theme.text_scale;
+        for (i, text) in lines.iter().enumerate() {
+            let width = measure_text(text, None, font_size as u16, 1.).width;
+            draw_text(
+                text,
+                screen_width() - width - 4.,
+                font_size * (i as f32 + 1.),
+                font_size,
+                RED,
+            );
+        }
+    }
+
+    fn set_status(&mut self, status: String) {
+        self.status = Some((status, get_time()));
+    }
+
+    fn draw_status(&mut self) {
+        if let Some((status, since)) = &self.status {
+            if get_time() - since > STATUS_SHOW_TIME {
+                self.status = None;
+            } else {
+                let font_size = 24. * self.theme.text_scale;
+                draw_text(status, 4., screen_height() - 6., font_size, RED);
+            }
+        }
+    }
+
+    fn record<F>(&mut self, write: F)
+    where
+        F: FnOnce(&mut Recorder) -> std::io::Result<()>,
+    {
+        if let Some(rec) = &mut self.recorder {
+            if let Err(e) = write(rec) {
+                eprintln!("replay recording stopped: {}", e);
+                self.recorder = None;
+            }
+        }
+    }
+
+    fn apply_replay_event(&mut self, event: ReplayEvent, mines: &[Cell]) -> bool {
+        match event {
+            ReplayEvent::Open(_) => return false,
+            ReplayEvent::Flag(crds, flag) => self.field.set_marked(crds, flag),
+            ReplayEvent::Update(cells) => self.field.update(cells),
+            ReplayEvent::Over(won, over_mines) if over_mines.is_empty() => {
+                self.field.reveal_mines(mines.to_vec(), won)
+            }
+            ReplayEvent::Over(won, over_mines) => self.field.reveal_mines(over_mines, won),
+            ReplayEvent::Undo => self.field.reset(),
+        }
+        true
+    }
+
+    // space - pause, right arrow - next step, up/down - speed x2 / x0.5
+    pub async fn playback(&mut self, replay: Replay) -> ! {
+        let mut events = replay.events.into_iter().peekable();
+        // the layout of a game recorded without its end is shown once the events run out
+        let mut over = false;
+        let mut clock_ms = 0.;
+        let mut speed = 1.;
+        let mut paused = false;
+        loop {
+            if is_key_pressed(KeyCode::Space) {
+                paused = !paused;
+            }
+            if is_key_pressed(KeyCode::Up) {
+                speed = f32::min(speed * 2., REPLAY_MAX_SPEED);
+            }
+            if is_key_pressed(KeyCode::Down) {
+                speed = f32::max(speed / 2., REPLAY_MIN_SPEED);
+            }
+            if is_key_pressed(KeyCode::Right) {
+                // skip to the next change of the board
+                for (ms, event) in events.by_ref() {
+                    clock_ms = ms as f32;
+                    over = is_replay_over(over, &event);
+                    if self.apply_replay_event(event, &replay.mines) {
+                        break;
+                    }
+                }
+            } else if !paused {
+                clock_ms += get_frame_time() * 1000. * speed;
+            }
+            while let Some((_, event)) = events.next_if(|(ms, _)| *ms as f32 <= clock_ms) {
+                over = is_replay_over(over, &event);
+                self.apply_replay_event(event, &replay.mines);
+            }
+            if events.peek().is_none() && !over {
+                self.field.reveal_mines(replay.mines.clone(), false);
+                over = true;
+            }
+
+            self.field.process_view_input();
+            self.process_accessibility_input();
+            self.field.draw(&self.theme);
+            let status = if events.peek().is_none() {
+                "end".to_string()
+            } else if paused {
+                "paused".to_string()
+            } else {
+                format!("x{}", speed)
+            };
+            let font_size = 24. * self.theme.text_scale;
+            draw_text(&status, 4., screen_height() - 6., font_size, RED);
+            self.draw_status();
+            next_frame().await;
+        }
+    }
+}
+
+// whether the mines are shown after the event, an undo takes back the end of the game
+fn is_replay_over(over: bool, event: &ReplayEvent) -> bool {
+    match event {
+        ReplayEvent::Over(..) => true,
+        ReplayEvent::Undo => false,
+        _ => over,
+    }
 }
 
 enum InputType {
@@ -55,210 +643,1214 @@ struct Input {
 
 #[derive(Copy, Clone, PartialEq)]
 enum VisibleCellState {
-    Empty(u8),
-    Mine,
+    Empty(i8),
+    // the weight of the cell, see CellState::weight
+    Mine(i8),
     Closed,
-    Marked,
-    BlownMine,
+    // the flag, see is_flag_weight
+    Marked(i8),
+    // not a flag for the server, chording and opening treat it as closed
+    Question,
+    // of the weight of the cell, one of many in a game of several lives
+    BlownMine(i8),
+    // a flag on a safe cell, shown once the game is over
+    WrongFlag,
 }
 trait TClientField {
-    fn process_input(&mut self) -> Option<Vec<Coords>>;
-    fn draw(&self);
+    fn process_input(&mut self, keys: &KeyBindings) -> Option<ClientMessage>;
+    // zoom and pan only, for when the board isn't played on
+    fn process_view_input(&mut self);
+    fn draw(&self, theme: &Theme);
     fn update(&mut self, update_pack: Vec<Cell>);
+    // 0 takes the flag off
+    fn set_marked(&mut self, crds: Coords, flag: i8);
+    fn set_question_marks(&mut self, enabled: bool);
+    // every cell closed again
+    fn reset(&mut self);
+    // a closed row at the bottom of a rect board, see TServerField::add_row
+    fn add_row(&mut self) {}
+    // a won game flags the rest of the mines, a lost one shows them
+    fn reveal_mines(&mut self, mines: Vec<Cell>, won: bool);
+}
+// the cells of a client field, with the input handling every field shares
+trait FieldCells {
+    fn state(&self, crds: Coords) -> VisibleCellState;
+    fn set_state(&mut self, crds: Coords, state: VisibleCellState);
+    fn neighbours(&self, crds: Coords) -> Vec<Coords>;
+    fn highlighted_cells(&mut self) -> &mut HashSet<Coords>;
+    // marking cycles closed -> flag -> question mark -> closed
+    fn question_marks(&self) -> bool;
+    // flags go through the weights of 1, 2 and 3 mines and of an anti-mine before coming off
+    fn multi_mines(&self) -> bool;
+
+    // question marks can be opened like any other unflagged cell
+    fn is_closed(&self, crds: Coords) -> bool {
+        matches!(
+            self.state(crds),
+            VisibleCellState::Closed | VisibleCellState::Question
+        )
+    }
+
+    fn apply_input(&mut self, input: Input) -> Option<ClientMessage> {
+        match input.inp_type {
+            InputType::HighlightCell => {
+                self.highlighted_cells().insert(input.coords);
+                None
+            }
+            InputType::HighlightNeighbours => {
+                let neighbours = self.neighbours(input.coords);
+                let highlighted_cells = self.highlighted_cells();
+                highlighted_cells.insert(input.coords);
+                highlighted_cells.extend(neighbours);
+                None
+            }
+            InputType::OpenCell => {
+                if self.is_closed(input.coords) {
+                    Some(ClientMessage::OpenCells(vec![input.coords]))
+                } else {
+                    None
+                }
+            }
+            // opens the closed neighbours of a number with flags and blown mines
+            // weighing as much around it
+            InputType::OpenCellNeighbours => {
+                let x_cnt = match self.state(input.coords) {
+                    VisibleCellState::Empty(x_cnt) => x_cnt as i32,
+                    _ => return None,
+                };
+                let neighbours = self.neighbours(input.coords);
+                let flags_cnt: i32 = neighbours
+                    .iter()
+                    .map(|&crds| match self.state(crds) {
+                        VisibleCellState::Marked(weight) | VisibleCellState::BlownMine(weight) => {
+                            weight as i32
+                        }
+                        _ => 0,
+                    })
+                    .sum();
+                let closed: Vec<Coords> = neighbours
+                    .into_iter()
+                    .filter(|&crds| self.is_closed(crds))
+                    .collect();
+                if flags_cnt != x_cnt || closed.is_empty() {
+                    return None;
+                }
+                Some(ClientMessage::OpenCells(closed))
+            }
+            InputType::MarkCell => {
+                let (state, flag) = match self.state(input.coords) {
+                    VisibleCellState::Closed => (VisibleCellState::Marked(1), Some(1)),
+                    VisibleCellState::Marked(flag) if self.multi_mines() && flag != -1 => {
+                        let flag = if flag == MAX_CELL_MINES as i8 {
+                            -1
+                        } else {
+                            flag + 1
+                        };
+                        (VisibleCellState::Marked(flag), Some(flag))
+                    }
+                    VisibleCellState::Marked(_) if self.question_marks() => {
+                        (VisibleCellState::Question, Some(0))
+                    }
+                    VisibleCellState::Marked(_) => (VisibleCellState::Closed, Some(0)),
+                    // question marks never leave the client
+                    VisibleCellState::Question => (VisibleCellState::Closed, None),
+                    _ => return None,
+                };
+                self.set_state(input.coords, state);
+                flag.map(|flag| ClientMessage::SetFlag {
+                    crds: input.coords,
+                    flag,
+                })
+            }
+        }
+    }
+}
+
+// the camera of a field and the input it keeps from frame to frame
+struct FieldView {
+    camera: BoardCamera,
+    // keyboard cursor at a row and col of the plane, see PlaneCells,
+    // hidden until a key moves it and again when the mouse is used
+    cursor: Option<(i64, i64)>,
+    // both mouse buttons went down, the first one released opens the neighbours
+    mouse_chord: bool,
+    highlighted_cells: HashSet<Coords>,
+}
+
+impl FieldView {
+    fn new(camera: BoardCamera) -> Self {
+        FieldView {
+            camera,
+            cursor: None,
+            mouse_chord: false,
+            highlighted_cells: HashSet::new(),
+        }
+    }
+}
+
+// the cells of a field at the rows and cols of the plane of its camera, a board has them
+// from 0,0, a torus all over the plane again and again and the endless board everywhere,
+// with the keyboard and mouse input and the drawing of square cells the fields share
+trait PlaneCells: FieldCells {
+    fn view(&self) -> &FieldView;
+    fn view_mut(&mut self) -> &mut FieldView;
+    // the cell at a row and col of the plane, if there's one
+    fn plane_cell(&self, row: i64, col: i64) -> Option<Coords>;
+    // the row and col the keyboard cursor shows up at, in the middle of the window
+    fn center_cell(&self) -> (i64, i64);
+
+    // rows and cells in a row the cells are in, None if they cover the plane
+    fn bounds(&self) -> Option<(usize, usize)> {
+        None
+    }
+
+    // rows and cells in a row a torus repeats at, its seams are drawn thicker
+    fn seams(&self) -> Option<(usize, usize)> {
+        None
+    }
+
+    // the square a cell fits in
+    fn cell_rect(&self, row: i64, col: i64) -> Rect {
+        Rect::new(col as f32, row as f32, 1., 1.)
+    }
+
+    fn cell_at_board(&self, pos: Vec2) -> Option<(i64, i64)> {
+        Some((pos.y.floor() as i64, pos.x.floor() as i64))
+    }
+
+    // drawn over a square cell at x, y in the window
+    fn draw_cell_hints(&self, _crds: Coords, _x: f32, _y: f32, _sq_size: f32, _theme: &Theme) {}
+
+    fn keyboard_input(&mut self, keys: &KeyBindings) -> Option<Input> {
+        let pressed = |keys: &[KeyCode]| keys.iter().any(|&key| is_key_pressed(key));
+        let moves = [
+            (&keys.up, -1, 0),
+            (&keys.down, 1, 0),
+            (&keys.left, 0, -1),
+            (&keys.right, 0, 1),
+        ];
+        for (move_keys, d_row, d_col) in moves {
+            if !pressed(move_keys) {
+                continue;
+            }
+            // the first press only shows the cursor in the middle of the window,
+            // an edge of the board stops it
+            let (row, col) = match self.view().cursor {
+                Some((row, col)) if self.plane_cell(row + d_row, col + d_col).is_some() => {
+                    (row + d_row, col + d_col)
+                }
+                Some(cursor) => cursor,
+                None => self.center_cell(),
+            };
+            let rect = self.cell_rect(row, col);
+            let view = self.view_mut();
+            view.cursor = Some((row, col));
+            view.camera.show(rect);
+        }
+
+        let (row, col) = self.view().cursor?;
+        Some(Input {
+            inp_type: key_input_type(keys)?,
+            coords: self.plane_cell(row, col)?,
+        })
+    }
+
+    fn mouse_input(&mut self) -> Option<Input> {
+        let inp_type = mouse_input_type(&mut self.view_mut().mouse_chord)?;
+        let view = self.view();
+        let (row, col) = self.cell_at_board(view.camera.to_board(Vec2::from(mouse_position())))?;
+        let coords = self.plane_cell(row, col)?;
+        self.view_mut().cursor = None;
+        Some(Input { inp_type, coords })
+    }
+
+    // only the cells inside the window, with every copy of the cursor cell on a torus
+    fn draw_squares(&self, theme: &Theme) {
+        let camera = &self.view().camera;
+        let sq_size = camera.scale();
+        let origin = camera.to_screen(Vec2::ZERO);
+        let view = camera.view_rect();
+        let (mut rows, mut cols) = (
+            view.y.floor() as i64..view.bottom().ceil() as i64,
+            view.x.floor() as i64..view.right().ceil() as i64,
+        );
+        if let Some((heigth, width)) = self.bounds() {
+            rows = rows.start.max(0)..rows.end.min(heigth as i64);
+            cols = cols.start.max(0)..cols.end.min(width as i64);
+        }
+
+        draw_rectangle(
+            origin.x + cols.start as f32 * sq_size,
+            origin.y + rows.start as f32 * sq_size,
+            sq_size * (cols.end - cols.start) as f32,
+            sq_size * (rows.end - rows.start) as f32,
+            theme.opened,
+        );
+
+        let cursor = self
+            .view()
+            .cursor
+            .and_then(|(row, col)| self.plane_cell(row, col));
+        let mut cursors = Vec::new();
+        for row in rows.clone() {
+            for col in cols.clone() {
+                let crds = match self.plane_cell(row, col) {
+                    Some(crds) => crds,
+                    None => continue,
+                };
+                let (x, y) = (
+                    origin.x + col as f32 * sq_size,
+                    origin.y + row as f32 * sq_size,
+                );
+                if cursor == Some(crds) {
+                    cursors.push((x, y));
+                }
+                draw_square_cell(
+                    self.state(crds),
+                    self.view().highlighted_cells.contains(&crds),
+                    x,
+                    y,
+                    sq_size,
+                    theme,
+                );
+                self.draw_cell_hints(crds, x, y, sq_size, theme);
+            }
+        }
+
+        for (x, y) in cursors {
+            draw_rectangle_lines(
+                x,
+                y,
+                sq_size,
+                sq_size,
+                f32::max(2., sq_size / 10.),
+                theme.cursor,
+            );
+        }
+
+        let seams = self.seams();
+        let line_thickness = |i: i64, size: Option<usize>| match size {
+            Some(size) if i.rem_euclid(size as i64) == 0 => WRAP_LINE_THICKNESS,
+            _ => GRID_LINE_THICKNESS,
+        };
+
+        // Draw vertical lines
+        for col in cols.start..=cols.end {
+            draw_line(
+                origin.x + col as f32 * sq_size,
+                origin.y + rows.start as f32 * sq_size,
+                origin.x + col as f32 * sq_size,
+                origin.y + rows.end as f32 * sq_size,
+                line_thickness(col, seams.map(|x| x.1)),
+                theme.grid_line,
+            );
+        }
+
+        // Draw horizontal grid lines
+        for row in rows.start..=rows.end {
+            draw_line(
+                origin.x + cols.start as f32 * sq_size,
+                origin.y + row as f32 * sq_size,
+                origin.x + cols.end as f32 * sq_size,
+                origin.y + row as f32 * sq_size,
+                line_thickness(row, seams.map(|x| x.0)),
+                theme.grid_line,
+            );
+        }
+    }
 }
-struct RectClientField {
+
+struct GridClientField {
     heigth: usize,
     width: usize,
+    #[allow(dead_code)]
     mines_cnt: usize,
-    rows: Vec<Vec<VisibleCellState>>,
-    highlighted_cells: HashSet<Coords>,
+    // indexed by the topology
+    cells: Vec<VisibleCellState>,
+    view: FieldView,
+    // marking cycles closed -> flag -> question mark -> closed
+    question_marks: bool,
+    multi_mines: bool,
+    // a torus is drawn repeated in every direction
+    grid_type: GridType,
+    // of a rect board that grows, see add_row
+    neighbourhood: Neighbourhood,
+    topology: Box<dyn Topology>,
+    // the layer of a cube that is shown, flat boards have only the 0th
+    layer: usize,
 }
 
 const SQ_SIZE: f32 = 30.;
 const GRID_LINE_THICKNESS: f32 = 1.0;
+const WRAP_LINE_THICKNESS: f32 = 3.0;
+const MAX_WINDOW_WIDTH: f32 = 1280.;
+const MAX_WINDOW_HEIGTH: f32 = 800.;
+const MINIMAP_SIZE: f32 = 150.;
+const MINIMAP_MARGIN: f32 = 10.;
+// of the cell size
+const GHOST_HINT_SIZE: f32 = 0.4;
+const GHOST_HINT_ALPHA: f32 = 0.5;
 
-impl RectClientField {
-    fn new(heigth: usize, width: usize, mines_cnt: usize) -> Self {
+impl GridClientField {
+    // the topology is the one of the grid type of the params
+    fn new(init_params: &InitParams, topology: Box<dyn Topology>) -> Self {
+        let grid_type = init_params.grid_type.clone();
+        let mines_cnt = init_params.mines_cnt;
+        let (heigth, width) = topology.size();
+        let cells_cnt = topology.cells_cnt();
         // check field params
-        if heigth == 0 || width == 0 || heigth * width - 1 < mines_cnt {
+        if cells_cnt == 0 || cells_cnt - 1 < mines_cnt {
             panic!("invalid field params!")
         }
-        set_window_size(
-            SQ_SIZE as u32 * width as u32,
-            SQ_SIZE as u32 * heigth as u32,
+        let board_size = board_size(&grid_type, heigth, width);
+        let bounds = match grid_type {
+            GridType::TorusGrid { .. } => CameraBounds::Wrap,
+            _ => CameraBounds::Board,
+        };
+        // just a starting size, the board is fit into whatever window there is
+        request_new_screen_size(
+            f32::min(SQ_SIZE * board_size.x, MAX_WINDOW_WIDTH),
+            f32::min(SQ_SIZE * board_size.y, MAX_WINDOW_HEIGTH),
         );
-        RectClientField {
+        GridClientField {
             heigth,
             width,
             mines_cnt,
-            rows: vec![vec![VisibleCellState::Closed; width]; heigth],
-            highlighted_cells: HashSet::new(),
+            cells: vec![VisibleCellState::Closed; cells_cnt],
+            view: FieldView::new(BoardCamera::new(board_size, bounds)),
+            question_marks: false,
+            multi_mines: init_params.multi_mines,
+            grid_type,
+            neighbourhood: init_params.neighbourhood.clone(),
+            topology,
+            layer: 0,
         }
     }
-}
-impl TClientField for RectClientField {
-    fn process_input(&mut self) -> Option<Vec<Coords>> {
-        let pos = mouse_position();
-        self.highlighted_cells.clear();
-        // check pos, if not ret None
-        let (row, col) = ((pos.1 / SQ_SIZE) as usize, (pos.0 / SQ_SIZE) as usize);
-        if is_mouse_button_down(MouseButton::Left) {
-            if is_mouse_button_down(MouseButton::Right) {
-                self.highlighted_cells
-                    .insert(Coords::RectCoords { row, col }); // todo: add neighbours
-                None
-            } else {
-                self.highlighted_cells
-                    .insert(Coords::RectCoords { row, col });
-                None
-            }
-        } else if is_mouse_button_released(MouseButton::Left) {
-            if !is_mouse_button_down(MouseButton::Right) {
-                if self.rows[row][col] == VisibleCellState::Closed {
-                    Some(vec![Coords::RectCoords { row, col }]) // todo: add neighbours
-                } else {
-                    None
-                }
-            } else {
-                if self.rows[row][col] == VisibleCellState::Closed {
-                    Some(vec![Coords::RectCoords { row, col }])
-                } else {
-                    None
+
+    // a cell of the shown layer
+    fn crds(&self, row: usize, col: usize) -> Coords {
+        self.grid_type.coords(self.layer, row, col)
+    }
+
+    fn wraps(&self) -> bool {
+        matches!(self.grid_type, GridType::TorusGrid { .. })
+    }
+
+    fn board_size(&self) -> Vec2 {
+        board_size(&self.grid_type, self.heigth, self.width)
+    }
+
+    fn layer_input(&mut self, keys: &KeyBindings) {
+        let pressed = |keys: &[KeyCode]| keys.iter().any(|&key| is_key_pressed(key));
+        let depth = self.grid_type.depth();
+        if pressed(&keys.layer_up) && self.layer > 0 {
+            self.layer -= 1;
+        } else if pressed(&keys.layer_down) && self.layer + 1 < depth {
+            self.layer += 1;
+        }
+    }
+
+    // the minimap is clicked or dragged to look at a part of the board, true if the mouse is over it
+    fn minimap_input(&mut self) -> bool {
+        let pos = Vec2::from(mouse_position());
+        match self.minimap_rect() {
+            Some(rect) if rect.contains(pos) => {
+                if is_mouse_button_down(MouseButton::Left) {
+                    let board_pos = (pos - rect.point()) * self.board_size().x / rect.w;
+                    self.view.camera.look_at(board_pos);
                 }
+                true
             }
-        } else if is_mouse_button_released(MouseButton::Right) {
-            match self.rows[row][col] {
-                VisibleCellState::Closed => self.rows[row][col] = VisibleCellState::Marked,
-                VisibleCellState::Marked => self.rows[row][col] = VisibleCellState::Closed,
-                _ => {}
-            }
-            None
-        } else {
-            None
+            _ => false,
         }
     }
 
-    fn draw(&self) {
-        clear_background(LIGHTGRAY);
-        let offset_x = 0.;
-        let offset_y = 0.;
+    // shown in the bottom right corner while the board doesn't fit in the window
+    fn minimap_rect(&self) -> Option<Rect> {
+        let size = self.board_size();
+        let board = Rect::new(0., 0., size.x, size.y);
+        let view = self.view.camera.view_rect();
+        if view.x <= board.x
+            && view.y <= board.y
+            && view.right() >= board.right()
+            && view.bottom() >= board.bottom()
+        {
+            return None;
+        }
+        let scale = MINIMAP_SIZE / f32::max(board.w, board.h);
+        let (w, h) = (board.w * scale, board.h * scale);
+        Some(Rect::new(
+            screen_width() - w - MINIMAP_MARGIN,
+            screen_height() - h - MINIMAP_MARGIN,
+            w,
+            h,
+        ))
+    }
 
-        draw_rectangle(
-            offset_x,
-            offset_y,
-            SQ_SIZE * self.width as f32,
-            SQ_SIZE * self.heigth as f32,
-            WHITE,
-        );
+    // triangles show glyphs, if any, rather than the square sprites
+    fn draw_tri(&self, theme: &Theme) {
+        let camera = &self.view.camera;
+        let view = camera.view_rect();
+        let rows = (view.y / TRI_ROW_HEIGTH).floor().max(0.) as usize
+            ..usize::min(
+                self.heigth,
+                (view.bottom() / TRI_ROW_HEIGTH).ceil().max(0.) as usize,
+            );
+        let cols = (view.x * 2. - 1.).floor().max(0.) as usize
+            ..usize::min(self.width, (view.right() * 2.).ceil().max(0.) as usize);
+        let scale = camera.scale();
 
-        for (row, cells) in self.rows.iter().enumerate() {
-            for (col, cell) in cells.iter().enumerate() {
-                match cell {
-                    VisibleCellState::BlownMine => {
-                        draw_rectangle(
-                            col as f32 * SQ_SIZE,
-                            row as f32 * SQ_SIZE,
-                            SQ_SIZE,
-                            SQ_SIZE,
-                            RED,
-                        );
-                    }
-                    VisibleCellState::Mine => {
-                        draw_rectangle(
-                            col as f32 * SQ_SIZE,
-                            row as f32 * SQ_SIZE,
-                            SQ_SIZE,
-                            SQ_SIZE,
-                            BLACK,
-                        );
+        for row in rows.clone() {
+            for col in cols.clone() {
+                let [a, b, c] = tri_vertices(row, col).map(|x| camera.to_screen(x));
+                let crds = self.crds(row, col);
+                let state = self.state(crds);
+                let fill = match state {
+                    VisibleCellState::Closed | VisibleCellState::Question
+                        if self.view.highlighted_cells.contains(&crds) =>
+                    {
+                        theme.highlighted
                     }
-                    VisibleCellState::Closed => {
-                        let crds = Coords::RectCoords { row, col };
-                        if self.highlighted_cells.contains(&crds) {
-                            draw_rectangle(
-                                col as f32 * SQ_SIZE,
-                                row as f32 * SQ_SIZE,
-                                SQ_SIZE,
-                                SQ_SIZE,
-                                WHITE,
-                            );
-                        } else {
-                            draw_rectangle(
-                                col as f32 * SQ_SIZE,
-                                row as f32 * SQ_SIZE,
-                                SQ_SIZE,
-                                SQ_SIZE,
-                                DARKGRAY,
-                            );
-                        }
-                    }
-                    VisibleCellState::Marked => {
-                        draw_rectangle(
-                            col as f32 * SQ_SIZE,
-                            row as f32 * SQ_SIZE,
-                            SQ_SIZE,
-                            SQ_SIZE,
-                            YELLOW,
-                        );
-                    }
-                    VisibleCellState::Empty(0) => {
-                        draw_rectangle(
-                            col as f32 * SQ_SIZE,
-                            row as f32 * SQ_SIZE,
-                            SQ_SIZE,
-                            SQ_SIZE,
-                            WHITE,
-                        );
+                    VisibleCellState::Closed | VisibleCellState::Question => theme.closed,
+                    VisibleCellState::Marked(_) | VisibleCellState::WrongFlag => theme.marked,
+                    VisibleCellState::Mine(_) => theme.mine,
+                    VisibleCellState::BlownMine(_) => theme.blown_mine,
+                    VisibleCellState::Empty(_) => theme.opened,
+                };
+                draw_triangle(a, b, c, fill);
+                draw_triangle_lines(a, b, c, GRID_LINE_THICKNESS, theme.grid_line);
+
+                // a square around the middle of the inscribed circle,
+                // which is a third of the way from the base to the tip
+                let cell = self.cell_rect(row as i64, col as i64);
+                let center_y = if tri_points_up(row, col) {
+                    cell.bottom() - TRI_ROW_HEIGTH / 3.
+                } else {
+                    cell.y + TRI_ROW_HEIGTH / 3.
+                };
+                let size = cell.w * scale;
+                let corner = camera.to_screen(vec2(cell.x, center_y - cell.w / 2.));
+                let (x, y) = (corner.x, corner.y);
+                let glyph = match state {
+                    VisibleCellState::Marked(_) => Some(Glyph::Flag),
+                    VisibleCellState::WrongFlag => Some(Glyph::WrongFlag),
+                    VisibleCellState::Mine(_) => Some(Glyph::Mine),
+                    VisibleCellState::BlownMine(_) => Some(Glyph::BlownMine),
+                    VisibleCellState::Question => {
+                        draw_cell_text("?", x, y, size, theme.question, theme);
+                        None
                     }
-                    VisibleCellState::Empty(x) => {
-                        draw_text(
-                            format!("{}", x).as_str(),
-                            (col as f32 + 0.3) as f32 * SQ_SIZE,
-                            (row as f32 + 0.75) * SQ_SIZE,
-                            SQ_SIZE,
-                            GOLD,
-                        );
+                    VisibleCellState::Closed | VisibleCellState::Empty(0) => None,
+                    VisibleCellState::Empty(x_cnt) => {
+                        let text = format!("{}", x_cnt);
+                        draw_cell_text(&text, x, y, size, theme.number(x_cnt), theme);
+                        None
                     }
-                    _ => {}
+                };
+                if let (Some(glyph), true) = (glyph, theme.glyphs) {
+                    draw_glyph(glyph, x, y, size, theme.glyph_color);
+                }
+                if let VisibleCellState::Mine(weight)
+                | VisibleCellState::Marked(weight)
+                | VisibleCellState::BlownMine(weight) = state
+                {
+                    draw_weight_label(weight, x, y, size, theme);
+                }
+                if self.view.cursor == Some((row as i64, col as i64)) {
+                    draw_triangle_lines(a, b, c, f32::max(2., scale / 10.), theme.cursor);
                 }
             }
         }
+    }
 
-        // Draw vertical lines
-        for col in 0..=self.width {
-            draw_line(
-                col as f32 * SQ_SIZE,
-                0.0,
-                col as f32 * SQ_SIZE,
-                self.heigth as f32 * SQ_SIZE,
-                GRID_LINE_THICKNESS,
-                GRAY,
-            );
+    fn draw_minimap(&self, rect: Rect, theme: &Theme) {
+        draw_rectangle(rect.x, rect.y, rect.w, rect.h, theme.closed);
+        let scale = rect.w / self.board_size().x;
+        for row in 0..self.heigth {
+            for col in 0..self.width {
+                let color = match self.state(self.crds(row, col)) {
+                    VisibleCellState::Closed | VisibleCellState::Question => continue,
+                    VisibleCellState::Marked(_) | VisibleCellState::WrongFlag => theme.marked,
+                    VisibleCellState::Mine(_) | VisibleCellState::BlownMine(_) => theme.blown_mine,
+                    VisibleCellState::Empty(_) => theme.opened,
+                };
+                let cell = self.cell_rect(row as i64, col as i64);
+                draw_rectangle(
+                    rect.x + cell.x * scale,
+                    rect.y + cell.y * scale,
+                    cell.w * scale,
+                    cell.h * scale,
+                    color,
+                );
+            }
         }
+        let view = self.view.camera.view_rect();
+        draw_rectangle_lines(
+            rect.x + view.x * scale,
+            rect.y + view.y * scale,
+            view.w * scale,
+            view.h * scale,
+            2.,
+            RED,
+        );
+    }
+}
+impl FieldCells for GridClientField {
+    // crds are on the board
+    fn state(&self, crds: Coords) -> VisibleCellState {
+        self.cells[self.topology.index(crds).expect("cell off the board")]
+    }
 
-        // Draw horizontal grid lines
-        for row in 0..=self.heigth {
-            draw_line(
-                0.0,
-                row as f32 * SQ_SIZE,
-                self.width as f32 * SQ_SIZE,
-                row as f32 * SQ_SIZE,
-                GRID_LINE_THICKNESS,
-                GRAY,
+    fn set_state(&mut self, crds: Coords, state: VisibleCellState) {
+        let i = self.topology.index(crds).expect("cell off the board");
+        self.cells[i] = state;
+    }
+
+    fn neighbours(&self, crds: Coords) -> Vec<Coords> {
+        self.topology.neighbours(crds)
+    }
+
+    fn highlighted_cells(&mut self) -> &mut HashSet<Coords> {
+        &mut self.view.highlighted_cells
+    }
+
+    fn question_marks(&self) -> bool {
+        self.question_marks
+    }
+
+    fn multi_mines(&self) -> bool {
+        self.multi_mines
+    }
+}
+
+impl PlaneCells for GridClientField {
+    fn view(&self) -> &FieldView {
+        &self.view
+    }
+
+    fn view_mut(&mut self) -> &mut FieldView {
+        &mut self.view
+    }
+
+    fn plane_cell(&self, row: i64, col: i64) -> Option<Coords> {
+        let (row, col) = self.topology.wrapped(row as isize, col as isize)?;
+        Some(self.crds(row, col))
+    }
+
+    fn center_cell(&self) -> (i64, i64) {
+        let center = self.view.camera.view_rect().center();
+        let center = center.clamp(Vec2::ZERO, self.board_size() - 0.5);
+        self.cell_at_board(center)
+            .unwrap_or((self.heigth as i64 / 2, self.width as i64 / 2))
+    }
+
+    fn bounds(&self) -> Option<(usize, usize)> {
+        (!self.wraps()).then_some((self.heigth, self.width))
+    }
+
+    fn seams(&self) -> Option<(usize, usize)> {
+        self.wraps().then_some((self.heigth, self.width))
+    }
+
+    // a triangle gets the middle half of its bounding box
+    fn cell_rect(&self, row: i64, col: i64) -> Rect {
+        match self.grid_type {
+            GridType::TriGrid { .. } => Rect::new(
+                col as f32 / 2. + 0.25,
+                row as f32 * TRI_ROW_HEIGTH,
+                0.5,
+                TRI_ROW_HEIGTH,
+            ),
+            _ => Rect::new(col as f32, row as f32, 1., 1.),
+        }
+    }
+
+    fn cell_at_board(&self, pos: Vec2) -> Option<(i64, i64)> {
+        match self.grid_type {
+            GridType::TriGrid { .. } => {
+                let row = (pos.y / TRI_ROW_HEIGTH).floor() as isize;
+                // a point is over one of the two triangles whose bounding boxes hold it
+                let col = (pos.x * 2.).floor() as isize;
+                [col - 1, col]
+                    .into_iter()
+                    .filter_map(|col| self.topology.wrapped(row, col))
+                    .find(|&(row, col)| {
+                        let [a, b, c] = tri_vertices(row, col);
+                        point_in_triangle(pos, a, b, c)
+                    })
+                    .map(|(row, col)| (row as i64, col as i64))
+            }
+            _ => Some((pos.y.floor() as i64, pos.x.floor() as i64)),
+        }
+    }
+
+    // faded numbers of the same cell on the layers above, in the top left corner,
+    // and below, in the bottom right one
+    fn draw_cell_hints(&self, crds: Coords, x: f32, y: f32, sq_size: f32, theme: &Theme) {
+        let (layer, row, col) = match crds {
+            Coords::CubeCoords { layer, row, col } => (layer, row, col),
+            _ => return,
+        };
+        let size = sq_size * GHOST_HINT_SIZE;
+        let hints = [
+            (layer.checked_sub(1), x, y),
+            (Some(layer + 1), x + sq_size - size, y + sq_size - size),
+        ];
+        for (layer, x, y) in hints {
+            let crds = match layer {
+                Some(layer) => self.grid_type.coords(layer, row, col),
+                None => continue,
+            };
+            if let Some(VisibleCellState::Empty(x_cnt)) =
+                self.topology.index(crds).map(|i| self.cells[i])
+            {
+                if x_cnt == 0 {
+                    continue;
+                }
+                let mut color = theme.number(x_cnt);
+                color.a *= GHOST_HINT_ALPHA;
+                draw_cell_text(&format!("{}", x_cnt), x, y, size, color, theme);
+            }
+        }
+    }
+}
+
+impl TClientField for GridClientField {
+    fn process_input(&mut self, keys: &KeyBindings) -> Option<ClientMessage> {
+        self.process_view_input();
+        self.view.highlighted_cells.clear();
+        self.layer_input(keys);
+        let input = match self.keyboard_input(keys) {
+            Some(input) => input,
+            None if self.minimap_input() => return None,
+            None => self.mouse_input()?,
+        };
+        self.apply_input(input)
+    }
+
+    fn process_view_input(&mut self) {
+        self.view.camera.process_input();
+    }
+
+    fn draw(&self, theme: &Theme) {
+        clear_background(theme.background);
+        match self.grid_type {
+            GridType::TriGrid { .. } => self.draw_tri(theme),
+            _ => self.draw_squares(theme),
+        }
+        if let Some(rect) = self.minimap_rect() {
+            self.draw_minimap(rect, theme);
+        }
+        let depth = self.grid_type.depth();
+        if depth > 1 {
+            let label = format!("layer {}/{}", self.layer + 1, depth);
+            draw_text(
+                &label,
+                4.,
+                20. * theme.text_scale,
+                24. * theme.text_scale,
+                RED,
             );
         }
     }
 
     fn update(&mut self, update_pack: Vec<Cell>) {
         for cell in update_pack {
-            if let Coords::RectCoords { row, col } = cell.crds {
-                self.rows[row][col] = match cell.state {
-                    CellState::Mine => VisibleCellState::BlownMine,
-                    CellState::Empty(x) => VisibleCellState::Empty(x),
+            if let Some(i) = self.topology.index(cell.crds) {
+                self.cells[i] = opened_state(cell.state);
+            }
+        }
+    }
+
+    fn set_marked(&mut self, crds: Coords, flag: i8) {
+        if let Some(i) = self.topology.index(crds) {
+            self.cells[i] = marked_state(self.cells[i], flag);
+        }
+    }
+
+    fn reveal_mines(&mut self, mines: Vec<Cell>, won: bool) {
+        let mut weights = vec![0; self.cells.len()];
+        for cell in mines {
+            if let Some(i) = self.topology.index(cell.crds) {
+                weights[i] = cell.state.weight();
+            }
+        }
+        for (state, weight) in self.cells.iter_mut().zip(weights) {
+            *state = game_over_state(*state, weight, won);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.cells = vec![VisibleCellState::Closed; self.cells.len()];
+        self.view.highlighted_cells.clear();
+    }
+
+    // the row-major indices of the cells above stay the same
+    fn add_row(&mut self) {
+        if let GridType::RectGrid { heigth, width } = self.grid_type {
+            self.cells
+                .extend(vec![VisibleCellState::Closed; self.width]);
+            self.heigth += 1;
+            self.grid_type = GridType::RectGrid {
+                heigth: heigth + 1,
+                width,
+            };
+            self.topology = Box::new(RectTopology {
+                heigth: heigth + 1,
+                width,
+                wrap: false,
+                offsets: self.neighbourhood.offsets(),
+            });
+            self.view.camera.set_board_size(self.board_size());
+        }
+    }
+
+    fn set_question_marks(&mut self, enabled: bool) {
+        self.question_marks = enabled;
+        if !enabled {
+            for state in self.cells.iter_mut() {
+                if *state == VisibleCellState::Question {
+                    *state = VisibleCellState::Closed;
                 }
+            }
+        }
+    }
+}
+
+fn opened_state(state: CellState) -> VisibleCellState {
+    match state {
+        CellState::Empty(x) => VisibleCellState::Empty(x),
+        state => VisibleCellState::BlownMine(state.weight()),
+    }
+}
+
+// a flag the server set or removed
+fn marked_state(state: VisibleCellState, flag: i8) -> VisibleCellState {
+    match (state, flag) {
+        (VisibleCellState::Marked(_), 0) => VisibleCellState::Closed,
+        (
+            VisibleCellState::Closed | VisibleCellState::Question | VisibleCellState::Marked(_),
+            flag,
+        ) if flag != 0 => VisibleCellState::Marked(flag),
+        (state, _) => state,
+    }
+}
+
+// a won game flags the rest of the mines, a lost one shows them and the wrong flags,
+// weight is of the cell, 0 for a safe one
+fn game_over_state(state: VisibleCellState, weight: i8, won: bool) -> VisibleCellState {
+    match (state, weight) {
+        (VisibleCellState::Closed | VisibleCellState::Question, weight) if weight != 0 => {
+            if won {
+                VisibleCellState::Marked(weight)
+            } else {
+                VisibleCellState::Mine(weight)
+            }
+        }
+        (VisibleCellState::Marked(_), 0) => VisibleCellState::WrongFlag,
+        // a flag of the wrong weight on a mine
+        (VisibleCellState::Marked(flag), weight) if flag != weight => {
+            if won {
+                VisibleCellState::Marked(weight)
             } else {
-                panic!("wrong coords type")
+                VisibleCellState::WrongFlag
             }
         }
+        (state, _) => state,
     }
 }
+
+fn key_input_type(keys: &KeyBindings) -> Option<InputType> {
+    let pressed = |keys: &[KeyCode]| keys.iter().any(|&key| is_key_pressed(key));
+    let down = |keys: &[KeyCode]| keys.iter().any(|&key| is_key_down(key));
+    let released = |keys: &[KeyCode]| keys.iter().any(|&key| is_key_released(key));
+    if released(&keys.open) {
+        Some(InputType::OpenCell)
+    } else if released(&keys.chord) {
+        Some(InputType::OpenCellNeighbours)
+    } else if pressed(&keys.mark) {
+        Some(InputType::MarkCell)
+    } else if down(&keys.chord) {
+        Some(InputType::HighlightNeighbours)
+    } else if down(&keys.open) {
+        Some(InputType::HighlightCell)
+    } else {
+        None
+    }
+}
+
+// both buttons down highlight the neighbours, the first one released opens them
+fn mouse_input_type(mouse_chord: &mut bool) -> Option<InputType> {
+    let (left, right) = (
+        is_mouse_button_down(MouseButton::Left),
+        is_mouse_button_down(MouseButton::Right),
+    );
+    let (left_released, right_released) = (
+        is_mouse_button_released(MouseButton::Left),
+        is_mouse_button_released(MouseButton::Right),
+    );
+    if left && right {
+        *mouse_chord = true;
+    }
+    let inp_type = if *mouse_chord {
+        // released while the other button is still down or released as well,
+        // so releasing the second button does nothing
+        let chord_released = (left_released && (right || right_released))
+            || (right_released && (left || left_released));
+        if !left && !right {
+            *mouse_chord = false;
+        }
+        if left && right {
+            InputType::HighlightNeighbours
+        } else if chord_released {
+            InputType::OpenCellNeighbours
+        } else {
+            return None;
+        }
+    } else if left {
+        InputType::HighlightCell
+    } else if left_released {
+        InputType::OpenCell
+    } else if right_released {
+        InputType::MarkCell
+    } else {
+        return None;
+    };
+    Some(inp_type)
+}
+
+// the square of a rect board cell at x, y in the window
+fn draw_square_cell(
+    state: VisibleCellState,
+    highlighted: bool,
+    x: f32,
+    y: f32,
+    sq_size: f32,
+    theme: &Theme,
+) {
+    let sprites = theme.sprites.as_ref();
+    match state {
+        // glyphs tell the cells apart by shape rather than by color
+        VisibleCellState::BlownMine(weight) if theme.glyphs => {
+            draw_rectangle(x, y, sq_size, sq_size, theme.blown_mine);
+            draw_glyph(Glyph::BlownMine, x, y, sq_size, theme.glyph_color);
+            draw_weight_label(weight, x, y, sq_size, theme);
+        }
+        VisibleCellState::Mine(weight) if theme.glyphs => {
+            draw_rectangle(x, y, sq_size, sq_size, theme.mine);
+            draw_glyph(Glyph::Mine, x, y, sq_size, theme.glyph_color);
+            draw_weight_label(weight, x, y, sq_size, theme);
+        }
+        VisibleCellState::Marked(weight) if theme.glyphs => {
+            draw_rectangle(x, y, sq_size, sq_size, theme.marked);
+            draw_glyph(Glyph::Flag, x, y, sq_size, theme.glyph_color);
+            draw_weight_label(weight, x, y, sq_size, theme);
+        }
+        VisibleCellState::WrongFlag if theme.glyphs => {
+            draw_rectangle(x, y, sq_size, sq_size, theme.marked);
+            draw_glyph(Glyph::WrongFlag, x, y, sq_size, theme.glyph_color);
+        }
+        VisibleCellState::BlownMine(weight) => {
+            match sprites {
+                Some(sprites) => sprites.draw(Sprite::BlownMine, x, y, sq_size),
+                None => draw_rectangle(x, y, sq_size, sq_size, theme.blown_mine),
+            }
+            draw_weight_label(weight, x, y, sq_size, theme);
+        }
+        VisibleCellState::Mine(weight) => {
+            draw_rectangle(x, y, sq_size, sq_size, theme.mine);
+            if let Some(sprites) = sprites {
+                sprites.draw(Sprite::Mine, x, y, sq_size);
+            }
+            draw_weight_label(weight, x, y, sq_size, theme);
+        }
+        state @ (VisibleCellState::Closed | VisibleCellState::Question) => {
+            if highlighted {
+                draw_rectangle(x, y, sq_size, sq_size, theme.highlighted);
+            } else {
+                match sprites {
+                    Some(sprites) => sprites.draw(Sprite::Closed, x, y, sq_size),
+                    None => draw_rectangle(x, y, sq_size, sq_size, theme.closed),
+                }
+            }
+            if state == VisibleCellState::Question {
+                draw_cell_text("?", x, y, sq_size, theme.question, theme);
+            }
+        }
+        VisibleCellState::Marked(weight) => {
+            match sprites {
+                Some(sprites) => {
+                    sprites.draw(Sprite::Closed, x, y, sq_size);
+                    sprites.draw(Sprite::Flag, x, y, sq_size);
+                }
+                None => draw_rectangle(x, y, sq_size, sq_size, theme.marked),
+            }
+            draw_weight_label(weight, x, y, sq_size, theme);
+        }
+        VisibleCellState::WrongFlag => {
+            match sprites {
+                Some(sprites) => {
+                    sprites.draw(Sprite::Closed, x, y, sq_size);
+                    sprites.draw(Sprite::Flag, x, y, sq_size);
+                }
+                None => draw_rectangle(x, y, sq_size, sq_size, theme.marked),
+            }
+            let thickness = f32::max(2., sq_size / 10.);
+            draw_cross(x, y, sq_size, thickness, theme.blown_mine);
+        }
+        VisibleCellState::Empty(0) => {}
+        VisibleCellState::Empty(x_cnt) => draw_cell_text(
+            &format!("{}", x_cnt),
+            x,
+            y,
+            sq_size,
+            theme.number(x_cnt),
+            theme,
+        ),
+    }
+}
+
+// endless board, drawn like a rect one, keeps the chunks in the window only
+struct EndlessClientField {
+    chunks: HashMap<(i64, i64), Vec<VisibleCellState>>,
+    // the chunks in the window the server was told of
+    shown_chunks: Vec<(i64, i64)>,
+    view: FieldView,
+    question_marks: bool,
+    topology: PlaneTopology,
+}
+
+// cells fit in the window at the start of an endless game
+const ENDLESS_VIEW_SIZE: Vec2 = Vec2::new(30., 20.);
+
+impl EndlessClientField {
+    fn new(neighbourhood: &Neighbourhood) -> Self {
+        request_new_screen_size(SQ_SIZE * ENDLESS_VIEW_SIZE.x, SQ_SIZE * ENDLESS_VIEW_SIZE.y);
+        let mut camera = BoardCamera::new(ENDLESS_VIEW_SIZE, CameraBounds::Unbounded);
+        // the cells around 0,0 are safe to start with
+        camera.look_at(vec2(0.5, 0.5));
+        EndlessClientField {
+            chunks: HashMap::new(),
+            shown_chunks: Vec::new(),
+            view: FieldView::new(camera),
+            question_marks: false,
+            topology: PlaneTopology {
+                offsets: neighbourhood.offsets(),
+            },
+        }
+    }
+
+    fn visible_chunks(&self) -> Vec<(i64, i64)> {
+        let view = self.view.camera.view_rect();
+        let (top, left) = chunk_of(view.y.floor() as i64, view.x.floor() as i64);
+        let (bottom, right) = chunk_of(view.bottom().floor() as i64, view.right().floor() as i64);
+        let mut res = Vec::new();
+        for chunk_row in top..=bottom {
+            for chunk_col in left..=right {
+                res.push((chunk_row, chunk_col));
+            }
+        }
+        res
+    }
+}
+
+impl FieldCells for EndlessClientField {
+    // closed for the chunks out of the window
+    fn state(&self, crds: Coords) -> VisibleCellState {
+        match crds {
+            Coords::PlaneCoords { row, col } => self
+                .chunks
+                .get(&chunk_of(row, col))
+                .map_or(VisibleCellState::Closed, |cells| {
+                    cells[chunk_index(row, col)]
+                }),
+            _ => VisibleCellState::Closed,
+        }
+    }
+
+    fn set_state(&mut self, crds: Coords, state: VisibleCellState) {
+        if let Coords::PlaneCoords { row, col } = crds {
+            self.chunks
+                .entry(chunk_of(row, col))
+                .or_insert_with(|| vec![VisibleCellState::Closed; CHUNK_CELLS])
+                [chunk_index(row, col)] = state;
+        }
+    }
+
+    fn neighbours(&self, crds: Coords) -> Vec<Coords> {
+        self.topology.neighbours(crds)
+    }
+
+    fn highlighted_cells(&mut self) -> &mut HashSet<Coords> {
+        &mut self.view.highlighted_cells
+    }
+
+    fn question_marks(&self) -> bool {
+        self.question_marks
+    }
+
+    // endless boards have single mines only
+    fn multi_mines(&self) -> bool {
+        false
+    }
+}
+
+impl PlaneCells for EndlessClientField {
+    fn view(&self) -> &FieldView {
+        &self.view
+    }
+
+    fn view_mut(&mut self) -> &mut FieldView {
+        &mut self.view
+    }
+
+    fn plane_cell(&self, row: i64, col: i64) -> Option<Coords> {
+        Some(Coords::PlaneCoords { row, col })
+    }
+
+    fn center_cell(&self) -> (i64, i64) {
+        let center = self.view.camera.view_rect().center();
+        (center.y.floor() as i64, center.x.floor() as i64)
+    }
+}
+
+impl TClientField for EndlessClientField {
+    fn process_input(&mut self, keys: &KeyBindings) -> Option<ClientMessage> {
+        self.process_view_input();
+        self.view.highlighted_cells.clear();
+        let visible = self.visible_chunks();
+        if visible != self.shown_chunks {
+            // the server sends the cells of the newly shown chunks
+            self.chunks.retain(|chunk, _| visible.contains(chunk));
+            for chunk in &visible {
+                self.chunks
+                    .entry(*chunk)
+                    .or_insert_with(|| vec![VisibleCellState::Closed; CHUNK_CELLS]);
+            }
+            self.shown_chunks = visible.clone();
+            return Some(ClientMessage::ShowChunks(visible));
+        }
+        let input = match self.keyboard_input(keys) {
+            Some(input) => input,
+            None => self.mouse_input()?,
+        };
+        self.apply_input(input)
+    }
+
+    fn process_view_input(&mut self) {
+        self.view.camera.process_input();
+    }
+
+    fn draw(&self, theme: &Theme) {
+        clear_background(theme.background);
+        self.draw_squares(theme);
+    }
+
+    fn update(&mut self, update_pack: Vec<Cell>) {
+        for cell in update_pack {
+            self.set_state(cell.crds, opened_state(cell.state));
+        }
+    }
+
+    fn set_marked(&mut self, crds: Coords, flag: i8) {
+        let state = marked_state(self.state(crds), flag);
+        self.set_state(crds, state);
+    }
+
+    fn reveal_mines(&mut self, mines: Vec<Cell>, won: bool) {
+        let weights: HashMap<Coords, i8> = mines
+            .into_iter()
+            .map(|cell| (cell.crds, cell.state.weight()))
+            .collect();
+        for (chunk, cells) in self.chunks.iter_mut() {
+            for (i, state) in cells.iter_mut().enumerate() {
+                let (row, col) = chunk_cell(*chunk, i);
+                let weight = weights
+                    .get(&Coords::PlaneCoords { row, col })
+                    .copied()
+                    .unwrap_or(0);
+                *state = game_over_state(*state, weight, won);
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        for cells in self.chunks.values_mut() {
+            cells.fill(VisibleCellState::Closed);
+        }
+        self.view.highlighted_cells.clear();
+    }
+
+    fn set_question_marks(&mut self, enabled: bool) {
+        self.question_marks = enabled;
+        if !enabled {
+            for state in self.chunks.values_mut().flatten() {
+                if *state == VisibleCellState::Question {
+                    *state = VisibleCellState::Closed;
+                }
+            }
+        }
+    }
+}
+
+// in board units, a rect cell and a triangle side are 1
+fn board_size(grid_type: &GridType, heigth: usize, width: usize) -> Vec2 {
+    match grid_type {
+        GridType::TriGrid { .. } => vec2((width + 1) as f32 / 2., heigth as f32 * TRI_ROW_HEIGTH),
+        _ => vec2(width as f32, heigth as f32),
+    }
+}
+
+// board points of the corners of a triangle
+fn tri_vertices(row: usize, col: usize) -> [Vec2; 3] {
+    let (left, top) = (col as f32 / 2., row as f32 * TRI_ROW_HEIGTH);
+    let bottom = top + TRI_ROW_HEIGTH;
+    if tri_points_up(row, col) {
+        [
+            vec2(left, bottom),
+            vec2(left + 1., bottom),
+            vec2(left + 0.5, top),
+        ]
+    } else {
+        [
+            vec2(left, top),
+            vec2(left + 1., top),
+            vec2(left + 0.5, bottom),
+        ]
+    }
+}
+
+// the point is on the same side of all three edges
+fn point_in_triangle(p: Vec2, a: Vec2, b: Vec2, c: Vec2) -> bool {
+    let side = |from: Vec2, to: Vec2| (to - from).perp_dot(p - from);
+    let (ab, bc, ca) = (side(a, b), side(b, c), side(c, a));
+    (ab >= 0. && bc >= 0. && ca >= 0.) || (ab <= 0. && bc <= 0. && ca <= 0.)
+}
+
+// the mines of a cell or a flag of other than a single mine, small in the bottom right corner
+fn draw_weight_label(weight: i8, x: f32, y: f32, sq_size: f32, theme: &Theme) {
+    let label = match weight {
+        1 => return,
+        -1 => "-".to_string(),
+        weight => weight.to_string(),
+    };
+    let size = sq_size / 2.;
+    draw_cell_text(&label, x + size, y + size, size, theme.glyph_color, theme);
+}
+
+// centered in the cell
+fn draw_cell_text(text: &str, x: f32, y: f32, sq_size: f32, color: Color, theme: &Theme) {
+    let font_size = (sq_size * theme.text_scale) as u16;
+    let size = measure_text(text, theme.font.as_ref(), font_size, 1.);
+    draw_text_ex(
+        text,
+        x + (sq_size - size.width) / 2.,
+        y + (sq_size + size.offset_y) / 2.,
+        TextParams {
+            font: theme.font.as_ref(),
+            font_size,
+            color,
+            ..Default::default()
+        },
+    );
+}
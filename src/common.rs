@@ -1,24 +1,906 @@
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+#[derive(Clone, PartialEq)]
 pub enum GridType {
-    RectGrid { heigth: usize, width: usize },
+    RectGrid {
+        heigth: usize,
+        width: usize,
+    },
+    // rect board wrapping at its edges, every cell has 8 neighbours
+    TorusGrid {
+        heigth: usize,
+        width: usize,
+    },
+    // rows of triangles pointing up and down in turn, see tri_points_up,
+    // a triangle touches up to 12 others by an edge or a vertex
+    TriGrid {
+        heigth: usize,
+        width: usize,
+    },
+    // layers of rect boards stacked on each other, every cell has 26 neighbours
+    CubeGrid {
+        heigth: usize,
+        width: usize,
+        depth: usize,
+    },
+    // a rect board without edges, generated a chunk at a time, see CHUNK_SIZE,
+    // the mines count of its InitParams is per chunk
+    EndlessGrid,
     HexGrid,
 }
+
+// cells along a side of the square chunks an endless board is generated in
+pub const CHUNK_SIZE: i64 = 16;
+pub const CHUNK_CELLS: usize = (CHUNK_SIZE * CHUNK_SIZE) as usize;
+// mines per chunk, a sparser board could open forever in one go,
+// the floor is the one of the standard neighbourhood, see Neighbourhood::min_chunk_mines
+pub const MIN_CHUNK_MINES: usize = CHUNK_CELLS / 8;
+pub const MAX_CHUNK_MINES: usize = CHUNK_CELLS / 2;
+
+// the chunk of an endless board cell
+pub fn chunk_of(row: i64, col: i64) -> (i64, i64) {
+    (row.div_euclid(CHUNK_SIZE), col.div_euclid(CHUNK_SIZE))
+}
+
+// the cells of a chunk are kept row by row
+pub fn chunk_index(row: i64, col: i64) -> usize {
+    (row.rem_euclid(CHUNK_SIZE) * CHUNK_SIZE + col.rem_euclid(CHUNK_SIZE)) as usize
+}
+
+pub fn chunk_cell(chunk: (i64, i64), index: usize) -> (i64, i64) {
+    let (chunk_row, chunk_col) = chunk;
+    (
+        chunk_row * CHUNK_SIZE + index as i64 / CHUNK_SIZE,
+        chunk_col * CHUNK_SIZE + index as i64 % CHUNK_SIZE,
+    )
+}
+
+// which cells count as the neighbours of a square cell, of rect, torus and endless boards,
+// tri and cube grids keep their own
+#[derive(Clone, PartialEq)]
+pub enum Neighbourhood {
+    // the 8 cells touching by an edge or a corner
+    Standard,
+    // the 4 cells touching by an edge
+    Cross,
+    // the 8 cells a chess knight jumps to
+    Knight,
+    // (d_row, d_col) of the neighbours, none of them 0,0 or listed twice
+    Offsets(Vec<(isize, isize)>),
+}
+
+// the farthest a neighbour can be along a row or a col, it keeps the counts below MAX_NEIGHBOURS
+pub const MAX_NEIGHBOUR_OFFSET: isize = 2;
+
+impl Neighbourhood {
+    pub fn offsets(&self) -> Vec<(isize, isize)> {
+        match self {
+            Neighbourhood::Standard => (-1..=1)
+                .flat_map(|d_row| (-1..=1).map(move |d_col| (d_row, d_col)))
+                .filter(|&offset| offset != (0, 0))
+                .collect(),
+            Neighbourhood::Cross => vec![(-1, 0), (0, -1), (0, 1), (1, 0)],
+            Neighbourhood::Knight => vec![
+                (-2, -1),
+                (-2, 1),
+                (-1, -2),
+                (-1, 2),
+                (1, -2),
+                (1, 2),
+                (2, -1),
+                (2, 1),
+            ],
+            Neighbourhood::Offsets(offsets) => offsets.clone(),
+        }
+    }
+
+    // the fewer the neighbours, the likelier a zero, so the floor rises as they get fewer
+    pub fn min_chunk_mines(&self) -> usize {
+        let cnt = self.offsets().len().max(1);
+        (MIN_CHUNK_MINES * 8)
+            .div_ceil(cnt)
+            .clamp(MIN_CHUNK_MINES, MAX_CHUNK_MINES)
+    }
+}
+
+// board headers may end with a neighbourhood other than the standard one,
+// it's told apart from the sizes by not being a number
+pub fn header_neighbourhood(words: &mut Vec<&str>) -> Result<Neighbourhood, String> {
+    match words.last() {
+        Some(word) if words.len() > 2 && word.parse::<usize>().is_err() => {
+            words.pop().unwrap().parse()
+        }
+        _ => Ok(Neighbourhood::Standard),
+    }
+}
+
+// heigth of a triangle row, the side of a triangle being 1
+pub const TRI_ROW_HEIGTH: f32 = 0.866_025_4;
+
+// the first triangle of the first row points up
+pub fn tri_points_up(row: usize, col: usize) -> bool {
+    (row + col).is_multiple_of(2)
+}
+
+impl GridType {
+    // rows and cells in a row, of a single layer for a cube, None for the endless board
+    pub fn size(&self) -> Option<(usize, usize)> {
+        match *self {
+            GridType::RectGrid { heigth, width }
+            | GridType::TorusGrid { heigth, width }
+            | GridType::TriGrid { heigth, width }
+            | GridType::CubeGrid { heigth, width, .. } => Some((heigth, width)),
+            GridType::EndlessGrid => None,
+            GridType::HexGrid => std::unimplemented!(),
+        }
+    }
+
+    // layers of a cube, flat boards have one
+    pub fn depth(&self) -> usize {
+        match *self {
+            GridType::CubeGrid { depth, .. } => depth,
+            _ => 1,
+        }
+    }
+
+    // the layer is ignored by flat boards
+    pub fn coords(&self, layer: usize, row: usize, col: usize) -> Coords {
+        match self {
+            GridType::CubeGrid { .. } => Coords::CubeCoords { layer, row, col },
+            _ => Coords::RectCoords { row, col },
+        }
+    }
+
+    // grid name used in the board files
+    pub fn kind(&self) -> &'static str {
+        match self {
+            GridType::RectGrid { .. } => "rect",
+            GridType::TorusGrid { .. } => "torus",
+            GridType::TriGrid { .. } => "tri",
+            GridType::CubeGrid { .. } => "cube",
+            GridType::EndlessGrid => "endless",
+            GridType::HexGrid => "hex",
+        }
+    }
+
+    // flat boards have a depth of 1
+    pub fn from_kind(kind: &str, heigth: usize, width: usize, depth: usize) -> Option<GridType> {
+        match kind {
+            "rect" if depth == 1 => Some(GridType::RectGrid { heigth, width }),
+            "torus" if depth == 1 => Some(GridType::TorusGrid { heigth, width }),
+            "tri" if depth == 1 => Some(GridType::TriGrid { heigth, width }),
+            "endless" if depth == 1 => Some(GridType::EndlessGrid),
+            "cube" => Some(GridType::CubeGrid {
+                heigth,
+                width,
+                depth,
+            }),
+            _ => None,
+        }
+    }
+
+    // the text forms of Coords, endless boards have signed coords
+    pub fn parse_coords(&self, s: &str) -> Result<Coords, String> {
+        match self {
+            GridType::EndlessGrid => {
+                let (row, col) = s
+                    .split_once(',')
+                    .ok_or_else(|| format!("bad coords: {}", s))?;
+                let parse = |x: &str| x.parse().map_err(|_| format!("bad coords: {}", s));
+                Ok(Coords::PlaneCoords {
+                    row: parse(row)?,
+                    col: parse(col)?,
+                })
+            }
+            _ => s.parse(),
+        }
+    }
+
+    // tri and cube grids have square cells
+    pub fn has_square_cells(&self) -> bool {
+        matches!(
+            self,
+            GridType::RectGrid { .. } | GridType::TorusGrid { .. } | GridType::EndlessGrid
+        )
+    }
+
+    // the neighbourhood is ignored by the grids without square cells,
+    // the endless board has no cells to index, see PlaneTopology
+    pub fn topology(&self, neighbourhood: &Neighbourhood) -> Option<Box<dyn Topology>> {
+        let topology: Box<dyn Topology> = match *self {
+            GridType::RectGrid { heigth, width } => Box::new(RectTopology {
+                heigth,
+                width,
+                wrap: false,
+                offsets: neighbourhood.offsets(),
+            }),
+            GridType::TorusGrid { heigth, width } => Box::new(RectTopology {
+                heigth,
+                width,
+                wrap: true,
+                offsets: neighbourhood.offsets(),
+            }),
+            GridType::TriGrid { heigth, width } => Box::new(TriTopology { heigth, width }),
+            GridType::CubeGrid {
+                heigth,
+                width,
+                depth,
+            } => Box::new(CubeTopology {
+                heigth,
+                width,
+                depth,
+            }),
+            GridType::EndlessGrid => return None,
+            GridType::HexGrid => std::unimplemented!(),
+        };
+        Some(topology)
+    }
+}
+
+// which cells touch, of the finite boards and of the endless one
+pub trait Neighbours {
+    // a cell is never its own neighbour and no neighbour is listed twice
+    fn neighbours(&self, crds: Coords) -> Vec<Coords>;
+}
+
+// the cells of a board and which of them touch, the fields keep
+// their per cell data in vectors indexed by it
+pub trait Topology: Neighbours {
+    fn cells_cnt(&self) -> usize;
+    // None for coords that are off the board or of another grid
+    fn index(&self, crds: Coords) -> Option<usize>;
+    // the cell with the given index
+    fn coords(&self, index: usize) -> Coords;
+    // rows and cells in a row, of a single layer for a cube
+    fn size(&self) -> (usize, usize);
+
+    // a row and col from anywhere on the plane, brought onto a torus or dropped off other boards
+    fn wrapped(&self, row: isize, col: isize) -> Option<(usize, usize)> {
+        let (heigth, width) = self.size();
+        wrapped(row, col, heigth, width, false)
+    }
+
+    // all cells in the order of their indices
+    fn cells(&self) -> Vec<Coords> {
+        (0..self.cells_cnt()).map(|i| self.coords(i)).collect()
+    }
+
+    fn neighbour_indices(&self, index: usize) -> Vec<usize> {
+        self.neighbours(self.coords(index))
+            .into_iter()
+            .filter_map(|crds| self.index(crds))
+            .collect()
+    }
+}
+
+// square cells with the neighbours at the offsets of a Neighbourhood, optionally wrapping into a torus
+pub struct RectTopology {
+    pub heigth: usize,
+    pub width: usize,
+    pub wrap: bool,
+    pub offsets: Vec<(isize, isize)>,
+}
+
+// see tri_points_up, a triangle touches up to 12 others by an edge or a vertex
+pub struct TriTopology {
+    pub heigth: usize,
+    pub width: usize,
+}
+
+pub struct CubeTopology {
+    pub heigth: usize,
+    pub width: usize,
+    pub depth: usize,
+}
+
+// the square cells of the endless board, with the neighbours at the offsets of a Neighbourhood
+pub struct PlaneTopology {
+    pub offsets: Vec<(isize, isize)>,
+}
+
+impl Topology for RectTopology {
+    fn cells_cnt(&self) -> usize {
+        self.heigth * self.width
+    }
+
+    fn index(&self, crds: Coords) -> Option<usize> {
+        row_major_index(crds, self.heigth, self.width)
+    }
+
+    fn coords(&self, index: usize) -> Coords {
+        row_major_coords(index, self.width)
+    }
+
+    fn size(&self) -> (usize, usize) {
+        (self.heigth, self.width)
+    }
+
+    fn wrapped(&self, row: isize, col: isize) -> Option<(usize, usize)> {
+        wrapped(row, col, self.heigth, self.width, self.wrap)
+    }
+}
+
+impl Neighbours for RectTopology {
+    fn neighbours(&self, crds: Coords) -> Vec<Coords> {
+        offset_neighbours(crds, self.heigth, self.width, self.wrap, &self.offsets)
+    }
+}
+
+impl Topology for TriTopology {
+    fn cells_cnt(&self) -> usize {
+        self.heigth * self.width
+    }
+
+    fn index(&self, crds: Coords) -> Option<usize> {
+        row_major_index(crds, self.heigth, self.width)
+    }
+
+    fn coords(&self, index: usize) -> Coords {
+        row_major_coords(index, self.width)
+    }
+
+    fn size(&self) -> (usize, usize) {
+        (self.heigth, self.width)
+    }
+}
+
+impl Neighbours for TriTopology {
+    // a triangle shares a vertex with 3 triangles of the row at its tip
+    // and an edge or a vertex with 5 triangles of the row at its base
+    fn neighbours(&self, crds: Coords) -> Vec<Coords> {
+        let points_up = match crds {
+            Coords::RectCoords { row, col } => tri_points_up(row, col),
+            _ => return Vec::new(),
+        };
+        let offsets: Vec<_> = (-1..=1)
+            .flat_map(|d_row| {
+                let reach = if d_row != 0 && (d_row < 0) == points_up {
+                    1
+                } else {
+                    2
+                };
+                (-reach..=reach).map(move |d_col| (d_row, d_col))
+            })
+            .collect();
+        offset_neighbours(crds, self.heigth, self.width, false, &offsets)
+    }
+}
+
+impl Topology for CubeTopology {
+    fn cells_cnt(&self) -> usize {
+        self.depth * self.heigth * self.width
+    }
+
+    fn index(&self, crds: Coords) -> Option<usize> {
+        match crds {
+            Coords::CubeCoords { layer, row, col }
+                if layer < self.depth && row < self.heigth && col < self.width =>
+            {
+                Some((layer * self.heigth + row) * self.width + col)
+            }
+            _ => None,
+        }
+    }
+
+    fn coords(&self, index: usize) -> Coords {
+        let layer_size = self.heigth * self.width;
+        Coords::CubeCoords {
+            layer: index / layer_size,
+            row: index % layer_size / self.width,
+            col: index % self.width,
+        }
+    }
+
+    fn size(&self) -> (usize, usize) {
+        (self.heigth, self.width)
+    }
+}
+
+impl Neighbours for CubeTopology {
+    fn neighbours(&self, crds: Coords) -> Vec<Coords> {
+        let (layer, row, col) = match crds {
+            Coords::CubeCoords { layer, row, col } => (layer, row, col),
+            _ => return Vec::new(),
+        };
+        let mut res = Vec::with_capacity(26);
+        for d_layer in -1..=1 {
+            for d_row in -1..=1 {
+                for d_col in -1..=1 {
+                    let neighbour = Coords::CubeCoords {
+                        layer: (layer as isize + d_layer) as usize,
+                        row: (row as isize + d_row) as usize,
+                        col: (col as isize + d_col) as usize,
+                    };
+                    // off the board below zero wraps to huge values and fails the index check
+                    if neighbour != crds && self.index(neighbour).is_some() {
+                        res.push(neighbour);
+                    }
+                }
+            }
+        }
+        res
+    }
+}
+
+impl Neighbours for PlaneTopology {
+    fn neighbours(&self, crds: Coords) -> Vec<Coords> {
+        let (row, col) = match crds {
+            Coords::PlaneCoords { row, col } => (row, col),
+            _ => return Vec::new(),
+        };
+        self.offsets
+            .iter()
+            .map(|&(d_row, d_col)| Coords::PlaneCoords {
+                row: row + d_row as i64,
+                col: col + d_col as i64,
+            })
+            .collect()
+    }
+}
+
+fn wrapped(
+    row: isize,
+    col: isize,
+    heigth: usize,
+    width: usize,
+    wrap: bool,
+) -> Option<(usize, usize)> {
+    let (heigth, width) = (heigth as isize, width as isize);
+    if wrap {
+        Some((
+            row.rem_euclid(heigth) as usize,
+            col.rem_euclid(width) as usize,
+        ))
+    } else if (0..heigth).contains(&row) && (0..width).contains(&col) {
+        Some((row as usize, col as usize))
+    } else {
+        None
+    }
+}
+
+fn row_major_index(crds: Coords, heigth: usize, width: usize) -> Option<usize> {
+    match crds {
+        Coords::RectCoords { row, col } if row < heigth && col < width => Some(row * width + col),
+        _ => None,
+    }
+}
+
+fn row_major_coords(index: usize, width: usize) -> Coords {
+    Coords::RectCoords {
+        row: index / width,
+        col: index % width,
+    }
+}
+
+// the cells at the (d_row, d_col) offsets from the cell,
+// a small torus can reach the same cell across both edges or the cell itself, it's listed once or not at all
+fn offset_neighbours(
+    crds: Coords,
+    heigth: usize,
+    width: usize,
+    wrap: bool,
+    offsets: &[(isize, isize)],
+) -> Vec<Coords> {
+    let (row, col) = match crds {
+        Coords::RectCoords { row, col } => (row, col),
+        _ => return Vec::new(),
+    };
+    let mut res = Vec::with_capacity(offsets.len());
+    for &(d_row, d_col) in offsets {
+        let (row, col) = match wrapped(
+            row as isize + d_row,
+            col as isize + d_col,
+            heigth,
+            width,
+            wrap,
+        ) {
+            Some(crds) => crds,
+            None => continue,
+        };
+        let neighbour = Coords::RectCoords { row, col };
+        if neighbour != crds && !res.contains(&neighbour) {
+            res.push(neighbour);
+        }
+    }
+    res
+}
+
+#[derive(Clone)]
 pub struct InitParams {
     pub grid_type: GridType,
     pub mines_cnt: usize,
+    // mines layout seed, random if not set, a seed has the same layout in every build and
+    // on every platform, the daily boards and the saved endless games rely on it
+    pub seed: Option<u64>,
+    // moves can be undone, the game is kept off the records
+    pub practice: bool,
+    pub neighbourhood: Neighbourhood,
+    // cells of up to MAX_CELL_MINES mines and anti-mines, mines_cnt being their weighted total
+    pub multi_mines: bool,
+    // mines that can be blown up before the game is lost, 1 for the classic game
+    pub lives: usize,
+    // games of other modes have a score rather than a time and are kept off the records
+    pub mode: GameMode,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum GameMode {
+    Classic,
+    // as many boards cleared as possible before the time is up,
+    // a cleared or a lost board is followed by a new one
+    TimeAttack { limit: Duration },
+    // a rect board gets a row of new mines at the bottom every period,
+    // clearing it doesn't end the game, a blown mine does
+    Survival { period: Duration },
+}
+
+// (name, heigth, width, mines_cnt)
+const PRESETS: [(&str, usize, usize, usize); 3] = [
+    ("beginner", 9, 9, 10),
+    ("intermediate", 16, 16, 40),
+    ("expert", 16, 30, 99),
+];
+
+impl InitParams {
+    pub fn preset(name: &str) -> Option<InitParams> {
+        PRESETS
+            .iter()
+            .find(|preset| preset.0 == name)
+            .map(|&(_, heigth, width, mines_cnt)| InitParams {
+                grid_type: GridType::RectGrid { heigth, width },
+                mines_cnt,
+                seed: None,
+                practice: false,
+                neighbourhood: Neighbourhood::Standard,
+                multi_mines: false,
+                lives: 1,
+                mode: GameMode::Classic,
+            })
+    }
+
+    // preset name, or "custom-<heigth>x<width>-<mines_cnt>" for other boards,
+    // "<torus|tri>-<heigth>x<width>-<mines_cnt>" for other grids
+    // "cube-<heigth>x<width>x<depth>-<mines_cnt>" for cubes
+    // and "endless-<mines_cnt per chunk>" for endless boards,
+    // followed by "-<neighbourhood>" unless it's the standard one
+    // and "-multi" for boards of multi-mines, "-<lives>lives" for games of more than one life
+    pub fn preset_name(&self) -> String {
+        let name = match self.grid_type {
+            GridType::RectGrid { heigth, width } => PRESETS
+                .iter()
+                .find(|preset| (preset.1, preset.2, preset.3) == (heigth, width, self.mines_cnt))
+                .map(|preset| preset.0.to_string())
+                .unwrap_or_else(|| format!("custom-{}x{}-{}", heigth, width, self.mines_cnt)),
+            GridType::TorusGrid { heigth, width } | GridType::TriGrid { heigth, width } => format!(
+                "{}-{}x{}-{}",
+                self.grid_type.kind(),
+                heigth,
+                width,
+                self.mines_cnt
+            ),
+            GridType::CubeGrid {
+                heigth,
+                width,
+                depth,
+            } => format!("cube-{}x{}x{}-{}", heigth, width, depth, self.mines_cnt),
+            GridType::EndlessGrid => format!("endless-{}", self.mines_cnt),
+            GridType::HexGrid => std::unimplemented!(),
+        };
+        let name = match self.neighbourhood {
+            Neighbourhood::Standard => name,
+            _ => format!("{}-{}", name, self.neighbourhood),
+        };
+        let name = if self.multi_mines {
+            format!("{}-multi", name)
+        } else {
+            name
+        };
+        if self.lives > 1 {
+            format!("{}-{}lives", name, self.lives)
+        } else {
+            name
+        }
+    }
+
+    // the params a game can be started with, the ones of the command line
+    // and the ones the clients of a lobby send
+    pub fn validate(&self) -> Result<(), String> {
+        if let GridType::HexGrid = self.grid_type {
+            return Err("hex boards aren't supported".to_string());
+        }
+        // the rules of the text form
+        if let Neighbourhood::Offsets(offsets) = &self.neighbourhood {
+            let bad_offset = |(i, offset): (usize, &(isize, isize))| {
+                *offset == (0, 0)
+                    || offset.0.abs() > MAX_NEIGHBOUR_OFFSET
+                    || offset.1.abs() > MAX_NEIGHBOUR_OFFSET
+                    || offsets[..i].contains(offset)
+            };
+            if offsets.is_empty() || offsets.iter().enumerate().any(bad_offset) {
+                return Err("bad neighbourhood".to_string());
+            }
+        }
+        if self.neighbourhood != Neighbourhood::Standard && !self.grid_type.has_square_cells() {
+            return Err("only rect, torus and endless boards take a neighbourhood".to_string());
+        }
+        match self.grid_type.size() {
+            Some((heigth, width)) => {
+                let cells_cnt = heigth
+                    .checked_mul(width)
+                    .and_then(|x| x.checked_mul(self.grid_type.depth()));
+                match cells_cnt {
+                    None | Some(0) => return Err("bad board size".to_string()),
+                    // one of the cells is kept safe
+                    Some(cells_cnt) if self.mines_cnt >= cells_cnt => {
+                        return Err("too many mines for the board".to_string())
+                    }
+                    _ => {}
+                }
+            }
+            None => {
+                let min_mines = self.neighbourhood.min_chunk_mines();
+                if !(min_mines..=MAX_CHUNK_MINES).contains(&self.mines_cnt) {
+                    return Err(format!(
+                        "endless boards of the neighbourhood have {} to {} mines per chunk",
+                        min_mines, MAX_CHUNK_MINES
+                    ));
+                }
+                if self.multi_mines {
+                    return Err("endless boards have single mines only".to_string());
+                }
+                if self.mode != GameMode::Classic {
+                    return Err("endless boards have no game modes".to_string());
+                }
+            }
+        }
+        if self.lives == 0 {
+            return Err("a game needs a life at least".to_string());
+        }
+        match self.mode {
+            GameMode::Survival { .. } if !matches!(self.grid_type, GridType::RectGrid { .. }) => {
+                Err("only rect boards grow in survival".to_string())
+            }
+            GameMode::Survival { period } if period.is_zero() => {
+                Err("row period must be positive".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash)]
 pub enum Coords {
-    RectCoords { row: usize, col: usize },
+    RectCoords {
+        row: usize,
+        col: usize,
+    },
+    CubeCoords {
+        layer: usize,
+        row: usize,
+        col: usize,
+    },
+    PlaneCoords {
+        row: i64,
+        col: i64,
+    },
     HexCoords {},
 }
 
+// of a cube cell, the most of any grid
+pub const MAX_NEIGHBOURS: u8 = 26;
+
 #[derive(Copy, Clone, PartialEq)]
 pub enum CellState {
-    Empty(u8),
-    Mine,
+    // the weights of the neighbours summed up, anti-mines can take it below zero
+    Empty(i8),
+    // of 1 to MAX_CELL_MINES mines
+    Mine(u8),
+    // blows up like a mine, takes one off the counts of its neighbours
+    AntiMine,
 }
+
+pub const MAX_CELL_MINES: u8 = 3;
+
+impl CellState {
+    pub fn is_mine(&self) -> bool {
+        !matches!(self, CellState::Empty(_))
+    }
+
+    // what the cell adds to the counts of its neighbours, the same as its flag
+    pub fn weight(&self) -> i8 {
+        match *self {
+            CellState::Empty(_) => 0,
+            CellState::Mine(x) => x as i8,
+            CellState::AntiMine => -1,
+        }
+    }
+}
+
+// flags are the weight of the mines the player puts on a cell, 0 for no flag
+pub fn is_flag_weight(flag: i8) -> bool {
+    flag == -1 || (1..=MAX_CELL_MINES as i8).contains(&flag)
+}
+#[derive(Copy, Clone)]
 pub struct Cell {
     pub crds: Coords,
     pub state: CellState,
 }
+
+// standard difficulty metrics of a generated board
+#[derive(Copy, Clone)]
+pub struct BoardStats {
+    // minimum number of clicks needed to clear the board
+    pub bbbv: usize,
+    // connected areas of zero cells, each opened with one click
+    pub openings: usize,
+    // connected groups of numbered cells that don't touch any opening
+    pub islands: usize,
+    pub zero_cells_percent: f32,
+}
+
+pub fn invalid_data<S: Into<String>>(msg: S) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+pub fn next_line<I>(lines: &mut I) -> io::Result<String>
+where
+    I: Iterator<Item = io::Result<String>>,
+{
+    lines
+        .next()
+        .unwrap_or_else(|| Err(invalid_data("unexpected end of file")))
+}
+
+// the records of a file of one per line, like the stats and the daily results,
+// a missing file has none yet
+pub fn load_lines<T, P>(path: P) -> io::Result<Vec<T>>
+where
+    T: FromStr<Err = String>,
+    P: AsRef<Path>,
+{
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut res = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            res.push(line.parse().map_err(invalid_data)?);
+        }
+    }
+    Ok(res)
+}
+
+pub fn save_lines<T: fmt::Display, P: AsRef<Path>>(path: P, records: &[T]) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    for record in records {
+        writeln!(out, "{}", record)?;
+    }
+    out.flush()
+}
+
+pub fn append_line<T: fmt::Display, P: AsRef<Path>>(path: P, record: &T) -> io::Result<()> {
+    let mut out = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(out, "{}", record)
+}
+
+// text forms used by the replay and save files: "row,col" or "layer,row,col",
+// read with GridType::parse_coords for signed coords,
+// "-26".."78" / "*", "**", "***" for cells of several mines / "-*" for an anti-mine
+// and "standard" / "cross" / "knight" / "d_row,d_col;d_row,d_col;..."
+
+impl fmt::Display for Coords {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Coords::RectCoords { row, col } => write!(f, "{},{}", row, col),
+            Coords::CubeCoords { layer, row, col } => write!(f, "{},{},{}", layer, row, col),
+            Coords::PlaneCoords { row, col } => write!(f, "{},{}", row, col),
+            Coords::HexCoords {} => std::unimplemented!(),
+        }
+    }
+}
+
+impl FromStr for Coords {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |x: &str| x.parse().map_err(|_| format!("bad coords: {}", s));
+        match s.split(',').collect::<Vec<_>>().as_slice() {
+            [row, col] => Ok(Coords::RectCoords {
+                row: parse(row)?,
+                col: parse(col)?,
+            }),
+            [layer, row, col] => Ok(Coords::CubeCoords {
+                layer: parse(layer)?,
+                row: parse(row)?,
+                col: parse(col)?,
+            }),
+            _ => Err(format!("bad coords: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for CellState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CellState::Empty(x) => write!(f, "{}", x),
+            CellState::Mine(x) => write!(f, "{}", "*".repeat(*x as usize)),
+            CellState::AntiMine => write!(f, "-*"),
+        }
+    }
+}
+
+impl FromStr for CellState {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let max_cnt = (MAX_NEIGHBOURS * MAX_CELL_MINES) as i8;
+        match s {
+            "-*" => Ok(CellState::AntiMine),
+            _ if !s.is_empty()
+                && s.len() <= MAX_CELL_MINES as usize
+                && s.chars().all(|x| x == '*') =>
+            {
+                Ok(CellState::Mine(s.len() as u8))
+            }
+            _ => match s.parse::<i8>() {
+                Ok(x) if (-(MAX_NEIGHBOURS as i8)..=max_cnt).contains(&x) => {
+                    Ok(CellState::Empty(x))
+                }
+                _ => Err(format!("bad cell state: {}", s)),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Neighbourhood {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Neighbourhood::Standard => write!(f, "standard"),
+            Neighbourhood::Cross => write!(f, "cross"),
+            Neighbourhood::Knight => write!(f, "knight"),
+            Neighbourhood::Offsets(offsets) => {
+                let offsets: Vec<String> = offsets
+                    .iter()
+                    .map(|(d_row, d_col)| format!("{},{}", d_row, d_col))
+                    .collect();
+                write!(f, "{}", offsets.join(";"))
+            }
+        }
+    }
+}
+
+impl FromStr for Neighbourhood {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad_neighbourhood = || format!("bad neighbourhood: {}", s);
+        match s {
+            "standard" => return Ok(Neighbourhood::Standard),
+            "cross" => return Ok(Neighbourhood::Cross),
+            "knight" => return Ok(Neighbourhood::Knight),
+            _ => {}
+        }
+        let mut offsets = Vec::new();
+        for offset in s.split(';') {
+            let (d_row, d_col) = offset.split_once(',').ok_or_else(bad_neighbourhood)?;
+            let parse = |x: &str| match x.parse::<isize>() {
+                Ok(x) if x.abs() <= MAX_NEIGHBOUR_OFFSET => Ok(x),
+                _ => Err(bad_neighbourhood()),
+            };
+            let offset = (parse(d_row)?, parse(d_col)?);
+            if offset == (0, 0) || offsets.contains(&offset) {
+                return Err(bad_neighbourhood());
+            }
+            offsets.push(offset);
+        }
+        Ok(Neighbourhood::Offsets(offsets))
+    }
+}
@@ -0,0 +1,191 @@
+use crate::common::*;
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// a calendar day, in UTC so every teammate has the same board at the same time
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Date {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+}
+
+impl Date {
+    pub fn today() -> Date {
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |x| x.as_secs());
+        Date::from_days((secs / 86400) as i64)
+    }
+
+    // days since 1970-01-01, the civil calendar of every day back to year 0
+    fn from_days(days: i64) -> Date {
+        let z = days + 719468;
+        let era = z.div_euclid(146097);
+        let doe = z.rem_euclid(146097);
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = (yoe + era * 400 + (month <= 2) as i64) as i32;
+        Date { year, month, day }
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+// "yyyy-mm-dd"
+impl FromStr for Date {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad_date = || format!("bad date: {}", s);
+        let mut parts = s.splitn(3, '-');
+        let mut next = || parts.next().ok_or_else(bad_date);
+        let (year, month, day) = (next()?, next()?, next()?);
+        let date = Date {
+            year: year.parse().map_err(|_| bad_date())?,
+            month: month.parse().map_err(|_| bad_date())?,
+            day: day.parse().map_err(|_| bad_date())?,
+        };
+        if !(1..=12).contains(&date.month) || !(1..=31).contains(&date.day) {
+            return Err(bad_date());
+        }
+        Ok(date)
+    }
+}
+
+// the seed of the daily board of a preset, see InitParams::preset_name,
+// fnv-1a so it stays the same across builds and platforms
+pub fn daily_seed(date: Date, preset: &str) -> u64 {
+    format!("{} {}", date, preset)
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+            (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+        })
+}
+
+// daily results file, one game per line:
+//   <yyyy-mm-dd> <preset> <player> <time ms> <won|lost> <3bv> <clicks>
+// only the first game of a player on a daily board counts,
+// the exported files and the shared leaderboard have the same format
+pub struct DailyResult {
+    pub date: Date,
+    pub preset: String,
+    pub player: String,
+    pub time: Duration,
+    pub won: bool,
+    pub bbbv: usize,
+    pub clicks: usize,
+}
+
+impl DailyResult {
+    fn is_same_game(&self, other: &DailyResult) -> bool {
+        (self.date, &self.preset, &self.player) == (other.date, &other.preset, &other.player)
+    }
+}
+
+impl fmt::Display for DailyResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {} {} {} {}",
+            self.date,
+            self.preset,
+            self.player,
+            self.time.as_millis(),
+            if self.won { "won" } else { "lost" },
+            self.bbbv,
+            self.clicks
+        )
+    }
+}
+
+impl FromStr for DailyResult {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad_result = || format!("bad daily result: {}", s);
+        let words: Vec<&str> = s.split_whitespace().collect();
+        let [date, preset, player, time, result, bbbv, clicks] = words.as_slice() else {
+            return Err(bad_result());
+        };
+        Ok(DailyResult {
+            date: date.parse()?,
+            preset: preset.to_string(),
+            player: player.to_string(),
+            time: Duration::from_millis(time.parse().map_err(|_| bad_result())?),
+            won: match *result {
+                "won" => true,
+                "lost" => false,
+                _ => return Err(bad_result()),
+            },
+            bbbv: bbbv.parse().map_err(|_| bad_result())?,
+            clicks: clicks.parse().map_err(|_| bad_result())?,
+        })
+    }
+}
+
+#[derive(Default)]
+pub struct DailyResults {
+    results: Vec<DailyResult>,
+}
+
+impl DailyResults {
+    // a missing file means no daily games were played yet
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<DailyResults> {
+        Ok(DailyResults {
+            results: load_lines(path)?,
+        })
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        save_lines(path, &self.results)
+    }
+
+    // false if the player already has a result of the board
+    pub fn append<P: AsRef<Path>>(path: P, result: &DailyResult) -> io::Result<bool> {
+        if Self::load(&path)?.contains(result) {
+            return Ok(false);
+        }
+        append_line(path, result)?;
+        Ok(true)
+    }
+
+    fn contains(&self, result: &DailyResult) -> bool {
+        self.results.iter().any(|x| x.is_same_game(result))
+    }
+
+    // the results missing from self, returns how many were added
+    pub fn merge(&mut self, other: DailyResults) -> usize {
+        let mut added = 0;
+        for result in other.results {
+            if !self.contains(&result) {
+                self.results.push(result);
+                added += 1;
+            }
+        }
+        self.results
+            .sort_by(|a, b| (a.date, &a.preset).cmp(&(b.date, &b.preset)));
+        added
+    }
+
+    // the wins by time, then the losses
+    pub fn leaderboard(&self, date: Date, preset: &str) -> Vec<&DailyResult> {
+        let mut res: Vec<&DailyResult> = self
+            .results
+            .iter()
+            .filter(|x| x.date == date && x.preset == preset)
+            .collect();
+        res.sort_by_key(|x| (!x.won, x.time));
+        res
+    }
+}
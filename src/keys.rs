@@ -0,0 +1,167 @@
+use crate::common::*;
+use macroquad::prelude::KeyCode;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// key bindings file, one "action = key key ..." per line, '#' starts a comment line:
+//   up, down, left, right - move the cursor
+//   open - hold to highlight the cell, release to open it
+//   mark - flag or unflag the cell
+//   chord - hold to highlight the neighbours, release to open them
+//   undo - take back the last move of a practice game
+//   layer_up, layer_down - show the previous or the next layer of a cube
+// keys are named like "Up", "Space", "Enter", "F", "Key1", "Kp5", "LeftShift"
+// actions missing from the file keep their default keys
+pub struct KeyBindings {
+    pub up: Vec<KeyCode>,
+    pub down: Vec<KeyCode>,
+    pub left: Vec<KeyCode>,
+    pub right: Vec<KeyCode>,
+    pub open: Vec<KeyCode>,
+    pub mark: Vec<KeyCode>,
+    pub chord: Vec<KeyCode>,
+    pub undo: Vec<KeyCode>,
+    pub layer_up: Vec<KeyCode>,
+    pub layer_down: Vec<KeyCode>,
+}
+
+// WASD, +/- and the F keys are taken by the camera and the client
+impl Default for KeyBindings {
+    fn default() -> Self {
+        KeyBindings {
+            up: vec![KeyCode::Up],
+            down: vec![KeyCode::Down],
+            left: vec![KeyCode::Left],
+            right: vec![KeyCode::Right],
+            open: vec![KeyCode::Space],
+            mark: vec![KeyCode::F],
+            chord: vec![KeyCode::C],
+            undo: vec![KeyCode::Backspace],
+            layer_up: vec![KeyCode::PageUp],
+            layer_down: vec![KeyCode::PageDown],
+        }
+    }
+}
+
+impl KeyBindings {
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<KeyBindings> {
+        let mut bindings = KeyBindings::default();
+        for line in fs::read_to_string(path)?.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (action, keys) = line
+                .split_once('=')
+                .ok_or_else(|| invalid_data(format!("bad key binding: {}", line)))?;
+            let keys = keys
+                .split_whitespace()
+                .map(|key| parse_key(key).ok_or_else(|| invalid_data(format!("bad key: {}", key))))
+                .collect::<io::Result<Vec<_>>>()?;
+            match action.trim() {
+                "up" => bindings.up = keys,
+                "down" => bindings.down = keys,
+                "left" => bindings.left = keys,
+                "right" => bindings.right = keys,
+                "open" => bindings.open = keys,
+                "mark" => bindings.mark = keys,
+                "chord" => bindings.chord = keys,
+                "undo" => bindings.undo = keys,
+                "layer_up" => bindings.layer_up = keys,
+                "layer_down" => bindings.layer_down = keys,
+                action => return Err(invalid_data(format!("unknown action: {}", action))),
+            }
+        }
+        Ok(bindings)
+    }
+}
+
+const KEY_NAMES: &[(&str, KeyCode)] = &[
+    ("Up", KeyCode::Up),
+    ("Down", KeyCode::Down),
+    ("Left", KeyCode::Left),
+    ("Right", KeyCode::Right),
+    ("Space", KeyCode::Space),
+    ("Enter", KeyCode::Enter),
+    ("Backspace", KeyCode::Backspace),
+    ("Insert", KeyCode::Insert),
+    ("Delete", KeyCode::Delete),
+    ("Home", KeyCode::Home),
+    ("End", KeyCode::End),
+    ("PageUp", KeyCode::PageUp),
+    ("PageDown", KeyCode::PageDown),
+    ("LeftShift", KeyCode::LeftShift),
+    ("RightShift", KeyCode::RightShift),
+    ("LeftControl", KeyCode::LeftControl),
+    ("RightControl", KeyCode::RightControl),
+    ("LeftAlt", KeyCode::LeftAlt),
+    ("RightAlt", KeyCode::RightAlt),
+    ("Comma", KeyCode::Comma),
+    ("Period", KeyCode::Period),
+    ("Slash", KeyCode::Slash),
+    ("Semicolon", KeyCode::Semicolon),
+    ("Apostrophe", KeyCode::Apostrophe),
+    ("LeftBracket", KeyCode::LeftBracket),
+    ("RightBracket", KeyCode::RightBracket),
+    ("Backslash", KeyCode::Backslash),
+    ("A", KeyCode::A),
+    ("B", KeyCode::B),
+    ("C", KeyCode::C),
+    ("D", KeyCode::D),
+    ("E", KeyCode::E),
+    ("F", KeyCode::F),
+    ("G", KeyCode::G),
+    ("H", KeyCode::H),
+    ("I", KeyCode::I),
+    ("J", KeyCode::J),
+    ("K", KeyCode::K),
+    ("L", KeyCode::L),
+    ("M", KeyCode::M),
+    ("N", KeyCode::N),
+    ("O", KeyCode::O),
+    ("P", KeyCode::P),
+    ("Q", KeyCode::Q),
+    ("R", KeyCode::R),
+    ("S", KeyCode::S),
+    ("T", KeyCode::T),
+    ("U", KeyCode::U),
+    ("V", KeyCode::V),
+    ("W", KeyCode::W),
+    ("X", KeyCode::X),
+    ("Y", KeyCode::Y),
+    ("Z", KeyCode::Z),
+    ("Key0", KeyCode::Key0),
+    ("Key1", KeyCode::Key1),
+    ("Key2", KeyCode::Key2),
+    ("Key3", KeyCode::Key3),
+    ("Key4", KeyCode::Key4),
+    ("Key5", KeyCode::Key5),
+    ("Key6", KeyCode::Key6),
+    ("Key7", KeyCode::Key7),
+    ("Key8", KeyCode::Key8),
+    ("Key9", KeyCode::Key9),
+    ("Kp0", KeyCode::Kp0),
+    ("Kp1", KeyCode::Kp1),
+    ("Kp2", KeyCode::Kp2),
+    ("Kp3", KeyCode::Kp3),
+    ("Kp4", KeyCode::Kp4),
+    ("Kp5", KeyCode::Kp5),
+    ("Kp6", KeyCode::Kp6),
+    ("Kp7", KeyCode::Kp7),
+    ("Kp8", KeyCode::Kp8),
+    ("Kp9", KeyCode::Kp9),
+    ("KpEnter", KeyCode::KpEnter),
+    ("KpDecimal", KeyCode::KpDecimal),
+    ("KpMultiply", KeyCode::KpMultiply),
+    ("KpDivide", KeyCode::KpDivide),
+    ("KpAdd", KeyCode::KpAdd),
+    ("KpSubtract", KeyCode::KpSubtract),
+];
+
+fn parse_key(name: &str) -> Option<KeyCode> {
+    KEY_NAMES
+        .iter()
+        .find(|(key_name, _)| *key_name == name)
+        .map(|&(_, key)| key)
+}
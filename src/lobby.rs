@@ -0,0 +1,527 @@
+use crate::common::*;
+use crate::messges::*;
+use crate::server::*;
+use std::collections::{BTreeMap, HashMap};
+
+// the rooms of the game server, every one with a game of its own
+#[derive(Default)]
+pub struct Lobby {
+    rooms: BTreeMap<RoomId, Room>,
+    // the room of every client in one
+    client_rooms: HashMap<ClientId, RoomId>,
+    next_client_id: ClientId,
+    next_room_id: RoomId,
+    // the players are on this machine, the games of the rooms can be saved and loaded
+    local: bool,
+}
+
+struct Room {
+    name: String,
+    init_params: InitParams,
+    server: Server,
+    // in the order they joined, with their ready checks, the first one is the host
+    members: Vec<(ClientId, bool)>,
+    // get what the members get, their moves are rejected, see Server::process_spectator_data
+    spectators: Vec<ClientId>,
+}
+
+impl Room {
+    fn info(&self, id: RoomId) -> RoomInfo {
+        RoomInfo {
+            id,
+            name: self.name.clone(),
+            preset: self.init_params.preset_name(),
+            members_cnt: self.members.len(),
+            spectators_cnt: self.spectators.len(),
+            in_game: self.server.is_in_game(),
+        }
+    }
+
+    fn host(&self) -> ClientId {
+        self.members[0].0
+    }
+
+    // the shown chunks of an endless board are the ones of the window of its only player
+    fn is_endless(&self) -> bool {
+        matches!(self.init_params.grid_type, GridType::EndlessGrid)
+    }
+
+    fn is_member(&self, client: ClientId) -> bool {
+        self.members.iter().any(|member| member.0 == client)
+    }
+
+    // to the members and the spectators
+    fn broadcast(&self, msg: impl Fn() -> LobbyResponse) -> Vec<(ClientId, LobbyResponse)> {
+        self.members
+            .iter()
+            .map(|member| member.0)
+            .chain(self.spectators.iter().copied())
+            .map(|client| (client, msg()))
+            .collect()
+    }
+
+    fn state(&self, id: RoomId) -> Vec<(ClientId, LobbyResponse)> {
+        self.broadcast(|| LobbyResponse::Room {
+            id,
+            name: self.name.clone(),
+            init_params: self.init_params.clone(),
+            members: self.members.clone(),
+            spectators: self.spectators.clone(),
+        })
+    }
+}
+
+impl Lobby {
+    pub fn new() -> Lobby {
+        Lobby::default()
+    }
+
+    // the lobby of the game played in the window
+    pub fn local() -> Lobby {
+        Lobby {
+            local: true,
+            ..Lobby::default()
+        }
+    }
+
+    pub fn connect(&mut self) -> ClientId {
+        self.next_client_id += 1;
+        self.next_client_id
+    }
+
+    pub fn disconnect(&mut self, client: ClientId) -> Vec<(ClientId, LobbyResponse)> {
+        self.leave_room(client)
+    }
+
+    // a room of a game already going on, a resumed or a laid out one, with the client as its host
+    pub fn open_room(
+        &mut self,
+        client: ClientId,
+        name: String,
+        server: Server,
+    ) -> Vec<(ClientId, LobbyResponse)> {
+        let error = |e: &str| vec![(client, LobbyResponse::Error(e.to_string()))];
+        if self.client_rooms.contains_key(&client) {
+            return error("already in a room");
+        }
+        match server.init_params() {
+            Some(init_params) => self.add_room(client, name, init_params, server),
+            None => error("no game to open a room of"),
+        }
+    }
+
+    // the game of the room of the client
+    pub fn server(&self, client: ClientId) -> Option<&Server> {
+        self.rooms
+            .get(self.client_rooms.get(&client)?)
+            .map(|room| &room.server)
+    }
+
+    // the responses with the clients they go to, some of them to the other members of the room
+    pub fn process_request(
+        &mut self,
+        client: ClientId,
+        request: LobbyRequest,
+    ) -> Vec<(ClientId, LobbyResponse)> {
+        let error = |e: &str| vec![(client, LobbyResponse::Error(e.to_string()))];
+        match request {
+            LobbyRequest::ListRooms => {
+                let rooms = self.rooms.iter().map(|(&id, room)| room.info(id)).collect();
+                vec![(client, LobbyResponse::Rooms(rooms))]
+            }
+            LobbyRequest::CreateRoom { name, init_params } => {
+                if self.client_rooms.contains_key(&client) {
+                    return error("already in a room");
+                }
+                if let Err(e) = init_params.validate() {
+                    return error(&e);
+                }
+                self.add_room(client, name, init_params, Server::new())
+            }
+            LobbyRequest::JoinRoom(id) | LobbyRequest::Spectate(id) => {
+                if self.client_rooms.contains_key(&client) {
+                    return error("already in a room");
+                }
+                let room = match self.rooms.get_mut(&id) {
+                    Some(room) if room.is_endless() => {
+                        return error("endless rooms have one player")
+                    }
+                    Some(room) => room,
+                    None => return error("no such room"),
+                };
+                if let LobbyRequest::Spectate(_) = request {
+                    room.spectators.push(client);
+                } else {
+                    room.members.push((client, false));
+                }
+                self.client_rooms.insert(client, id);
+                let mut res = room.state(id);
+                // a game already going on is joined as it is
+                let view = room.server.game_view();
+                if !view.is_empty() {
+                    res.push((client, LobbyResponse::Game(view)));
+                }
+                res
+            }
+            LobbyRequest::LeaveRoom => {
+                if !self.client_rooms.contains_key(&client) {
+                    return error("not in a room");
+                }
+                self.leave_room(client)
+            }
+            LobbyRequest::SetReady(ready) => {
+                let (id, room) = match self.room_of(client) {
+                    Some(room) if room.1.is_member(client) => room,
+                    Some(_) => return error("spectators have no ready check"),
+                    None => return error("not in a room"),
+                };
+                for member in room.members.iter_mut().filter(|member| member.0 == client) {
+                    member.1 = ready;
+                }
+                room.state(id)
+            }
+            LobbyRequest::SetParams(init_params) => {
+                let (id, room) = match self.room_of(client) {
+                    Some(room) if room.1.host() == client => room,
+                    Some(_) => return error("only the host can change the game"),
+                    None => return error("not in a room"),
+                };
+                if let Err(e) = init_params.validate() {
+                    return error(&e);
+                }
+                if matches!(init_params.grid_type, GridType::EndlessGrid) && room.members.len() > 1
+                {
+                    return error("endless rooms have one player");
+                }
+                room.init_params = init_params;
+                // the ready checks were for the old game
+                for member in &mut room.members {
+                    member.1 = false;
+                }
+                room.state(id)
+            }
+            LobbyRequest::Kick(kicked) => {
+                match self.room_of(client) {
+                    Some((_, room)) if room.host() != client => {
+                        return error("only the host can kick")
+                    }
+                    Some((_, room))
+                        if kicked != client
+                            && (room.is_member(kicked) || room.spectators.contains(&kicked)) => {}
+                    Some(_) => return error("no such member or spectator"),
+                    None => return error("not in a room"),
+                }
+                self.leave_room(kicked)
+            }
+            LobbyRequest::StartGame => {
+                let (id, room) = match self.room_of(client) {
+                    Some(room) if room.1.host() == client => room,
+                    Some(_) => return error("only the host can start the game"),
+                    None => return error("not in a room"),
+                };
+                if room.members.iter().any(|&(_, ready)| !ready) {
+                    return error("not everyone is ready");
+                }
+                room.server.new_game(&room.init_params);
+                // a new ready check for the next game
+                for member in &mut room.members {
+                    member.1 = false;
+                }
+                let view = room.server.game_view();
+                let mut res = room.state(id);
+                res.extend(room.broadcast(|| LobbyResponse::Game(view.clone())));
+                res
+            }
+            LobbyRequest::Game(msg) => {
+                // the files are the server's ones, not the client's
+                let file_request =
+                    matches!(msg, ClientMessage::SaveGame(_) | ClientMessage::LoadGame(_));
+                if file_request && !self.local {
+                    return error("games of a room can't be saved or loaded");
+                }
+                let (_, room) = match self.room_of(client) {
+                    Some(room) => room,
+                    None => return error("not in a room"),
+                };
+                let flagged = match msg {
+                    ClientMessage::SetFlag { crds, .. } => Some(crds),
+                    _ => None,
+                };
+                let response = if room.is_member(client) {
+                    room.server.process_client_data(msg)
+                } else {
+                    room.server.process_spectator_data(msg)
+                };
+                let (errors, response): (Vec<_>, Vec<_>) = response
+                    .into_iter()
+                    .partition(|msg| matches!(msg, ServerMessage::Error(_)));
+                // a loaded game is the one of the room now
+                if let (true, Some(init_params)) =
+                    (file_request && errors.is_empty(), room.server.init_params())
+                {
+                    room.init_params = init_params;
+                }
+                // the errors are the sender's own
+                let rejected = !errors.is_empty();
+                let mut res = Vec::new();
+                if rejected {
+                    res.push((client, LobbyResponse::Game(errors)));
+                }
+                if !response.is_empty() {
+                    res.extend(room.broadcast(|| LobbyResponse::Game(response.clone())));
+                }
+                // a flag is answered with nothing, the sender has it already
+                if let (Some(crds), false) = (flagged, rejected) {
+                    let marked = vec![ServerMessage::Marked(vec![(crds, room.server.flag(crds))])];
+                    res.extend(
+                        room.broadcast(|| LobbyResponse::Game(marked.clone()))
+                            .into_iter()
+                            .filter(|&(member, _)| member != client),
+                    );
+                }
+                res
+            }
+        }
+    }
+
+    // the messages the games of the rooms push, called every frame
+    pub fn tick(&mut self) -> Vec<(ClientId, LobbyResponse)> {
+        let mut res = Vec::new();
+        for room in self.rooms.values_mut() {
+            let pushed = room.server.tick();
+            if !pushed.is_empty() {
+                res.extend(room.broadcast(|| LobbyResponse::Game(pushed.clone())));
+            }
+        }
+        res
+    }
+
+    fn add_room(
+        &mut self,
+        client: ClientId,
+        name: String,
+        init_params: InitParams,
+        server: Server,
+    ) -> Vec<(ClientId, LobbyResponse)> {
+        self.next_room_id += 1;
+        let id = self.next_room_id;
+        let room = Room {
+            name,
+            init_params,
+            server,
+            members: vec![(client, false)],
+            spectators: Vec::new(),
+        };
+        let res = room.state(id);
+        self.rooms.insert(id, room);
+        self.client_rooms.insert(client, id);
+        res
+    }
+
+    fn room_of(&mut self, client: ClientId) -> Option<(RoomId, &mut Room)> {
+        let id = *self.client_rooms.get(&client)?;
+        self.rooms.get_mut(&id).map(|room| (id, room))
+    }
+
+    // the next member becomes the host if it was the host leaving,
+    // a room left without members is torn down, its spectators leave it too
+    fn leave_room(&mut self, client: ClientId) -> Vec<(ClientId, LobbyResponse)> {
+        let id = match self.client_rooms.remove(&client) {
+            Some(id) => id,
+            None => return vec![],
+        };
+        let mut res = vec![(client, LobbyResponse::Left)];
+        let room = self.rooms.get_mut(&id).unwrap();
+        room.members.retain(|member| member.0 != client);
+        room.spectators.retain(|&spectator| spectator != client);
+        if room.members.is_empty() {
+            for &spectator in &room.spectators {
+                self.client_rooms.remove(&spectator);
+                res.push((spectator, LobbyResponse::Left));
+            }
+            self.rooms.remove(&id);
+        } else {
+            res.extend(room.state(id));
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(grid_type: GridType, mines_cnt: usize) -> InitParams {
+        InitParams {
+            grid_type,
+            mines_cnt,
+            seed: Some(1),
+            practice: false,
+            neighbourhood: Neighbourhood::Standard,
+            multi_mines: false,
+            lives: 1,
+            mode: GameMode::Classic,
+        }
+    }
+
+    fn rect_params() -> InitParams {
+        params(
+            GridType::RectGrid {
+                heigth: 9,
+                width: 9,
+            },
+            10,
+        )
+    }
+
+    // a game started in a room of the host and another player
+    fn two_players() -> (Lobby, ClientId, ClientId) {
+        let mut lobby = Lobby::new();
+        let (host, player) = (lobby.connect(), lobby.connect());
+        lobby.process_request(
+            host,
+            LobbyRequest::CreateRoom {
+                name: "room".to_string(),
+                init_params: rect_params(),
+            },
+        );
+        lobby.process_request(player, LobbyRequest::JoinRoom(1));
+        for client in [host, player] {
+            lobby.process_request(client, LobbyRequest::SetReady(true));
+        }
+        lobby.process_request(host, LobbyRequest::StartGame);
+        (lobby, host, player)
+    }
+
+    fn is_error(response: &LobbyResponse) -> bool {
+        matches!(response, LobbyResponse::Error(_))
+    }
+
+    #[test]
+    fn bad_params_are_rejected() {
+        let mut lobby = Lobby::new();
+        let host = lobby.connect();
+        for init_params in [
+            params(GridType::HexGrid, 10),
+            params(
+                GridType::RectGrid {
+                    heigth: 0,
+                    width: 9,
+                },
+                0,
+            ),
+            params(
+                GridType::RectGrid {
+                    heigth: 3,
+                    width: 3,
+                },
+                9,
+            ),
+            params(GridType::EndlessGrid, MAX_CHUNK_MINES + 1),
+            InitParams {
+                neighbourhood: Neighbourhood::Knight,
+                ..params(
+                    GridType::TriGrid {
+                        heigth: 8,
+                        width: 8,
+                    },
+                    10,
+                )
+            },
+            InitParams {
+                multi_mines: true,
+                ..params(GridType::EndlessGrid, MIN_CHUNK_MINES)
+            },
+            InitParams {
+                lives: 0,
+                ..rect_params()
+            },
+        ] {
+            let res = lobby.process_request(
+                host,
+                LobbyRequest::CreateRoom {
+                    name: "room".to_string(),
+                    init_params,
+                },
+            );
+            assert!(
+                matches!(res.as_slice(), [(client, response)] if *client == host && is_error(response))
+            );
+        }
+        assert!(lobby.rooms.is_empty());
+    }
+
+    #[test]
+    fn flags_reach_the_other_members() {
+        let (mut lobby, host, player) = two_players();
+        let crds = Coords::RectCoords { row: 4, col: 4 };
+        let res = lobby.process_request(
+            host,
+            LobbyRequest::Game(ClientMessage::SetFlag { crds, flag: 1 }),
+        );
+        assert!(matches!(
+            res.as_slice(),
+            [(client, LobbyResponse::Game(msgs))]
+                if *client == player
+                    && matches!(msgs.as_slice(), [ServerMessage::Marked(flags)] if flags == &[(crds, 1)])
+        ));
+    }
+
+    #[test]
+    fn errors_go_to_the_sender_only() {
+        let (mut lobby, _, player) = two_players();
+        let res = lobby.process_request(player, LobbyRequest::Game(ClientMessage::Undo));
+        assert!(matches!(
+            res.as_slice(),
+            [(client, LobbyResponse::Game(msgs))]
+                if *client == player && matches!(msgs.as_slice(), [ServerMessage::Error(_)])
+        ));
+    }
+
+    #[test]
+    fn spectators_watch_without_moving() {
+        let (mut lobby, host, player) = two_players();
+        let spectator = lobby.connect();
+        lobby.process_request(spectator, LobbyRequest::Spectate(1));
+        let crds = Coords::RectCoords { row: 4, col: 4 };
+        let res = lobby.process_request(
+            host,
+            LobbyRequest::Game(ClientMessage::SetFlag { crds, flag: 1 }),
+        );
+        let receivers: Vec<ClientId> = res.iter().map(|x| x.0).collect();
+        assert_eq!(receivers, vec![player, spectator]);
+        let res = lobby.process_request(
+            spectator,
+            LobbyRequest::Game(ClientMessage::SetFlag { crds, flag: 0 }),
+        );
+        assert!(matches!(
+            res.as_slice(),
+            [(client, LobbyResponse::Game(msgs))]
+                if *client == spectator && matches!(msgs.as_slice(), [ServerMessage::Error(_)])
+        ));
+        let res = lobby.process_request(spectator, LobbyRequest::SetReady(true));
+        assert!(matches!(res.as_slice(), [(_, response)] if is_error(response)));
+        // the room is torn down with its last member
+        lobby.process_request(host, LobbyRequest::LeaveRoom);
+        let res = lobby.process_request(player, LobbyRequest::LeaveRoom);
+        assert!(res
+            .iter()
+            .any(|x| x.0 == spectator && matches!(x.1, LobbyResponse::Left)));
+        assert!(lobby.server(spectator).is_none());
+    }
+
+    #[test]
+    fn endless_rooms_have_one_player() {
+        let mut lobby = Lobby::new();
+        let (host, player) = (lobby.connect(), lobby.connect());
+        lobby.process_request(
+            host,
+            LobbyRequest::CreateRoom {
+                name: "room".to_string(),
+                init_params: params(GridType::EndlessGrid, MIN_CHUNK_MINES),
+            },
+        );
+        let res = lobby.process_request(player, LobbyRequest::JoinRoom(1));
+        assert!(matches!(res.as_slice(), [(_, response)] if is_error(response)));
+    }
+}
@@ -1,33 +1,298 @@
-use macroquad::prelude::*;
-
+pub mod camera;
 pub mod client;
 pub mod common;
+pub mod daily;
+pub mod keys;
+pub mod lobby;
+pub mod messges;
+pub mod replay;
 pub mod server;
+pub mod stats;
+pub mod theme;
 use crate::client::*;
 use crate::common::*;
+use crate::daily::*;
+use crate::keys::*;
+use crate::lobby::*;
+use crate::messges::*;
+use crate::replay::*;
 use crate::server::*;
+use crate::theme::*;
+use macroquad::window::Conf;
+use std::path::Path;
+use std::time::Duration;
+
+// the options of a new board, an interrupted game is resumed only without them
+const BOARD_ARGS: &[&str] = &[
+    "--preset",
+    "--grid",
+    "--depth",
+    "--neighbours",
+    "--multi-mines",
+    "--lives",
+    "--seed",
+    "--practice",
+    "--time-attack",
+    "--survival",
+    "--daily",
+];
 
-// fn init_client_field(params: &InitParams) -> Box<dyn TClientField> {
-//     Box::new(match params.grid_type {
-//         GridType::RectGrid{width, heigth} => RectClientField::new(width, heigth, params.mines_cnt),
-//         GridType::HexGrid => panic!("Not implemented")
-//     })
-// }
+// the game messages of the local player, the errors of the lobby are shown like the game ones
+fn local_messages(
+    player: ClientId,
+    responses: Vec<(ClientId, LobbyResponse)>,
+) -> Vec<ServerMessage> {
+    let mut res = Vec::new();
+    for (client, response) in responses {
+        match response {
+            _ if client != player => {}
+            LobbyResponse::Game(msgs) => res.extend(msgs),
+            LobbyResponse::Error(e) => res.push(ServerMessage::Error(e)),
+            _ => {}
+        }
+    }
+    res
+}
 
-#[macroquad::main("Rs-Mines")]
+fn window_conf() -> Conf {
+    Conf {
+        window_title: "Rs-Mines".to_owned(),
+        window_resizable: true,
+        high_dpi: true,
+        ..Default::default()
+    }
+}
+
+#[macroquad::main(window_conf)]
 async fn main() {
-    let params = InitParams {
-        grid_type: GridType::RectGrid {
-            heigth: 20,
-            width: 30,
+    // rs-minesweeper [--theme <name|file>]
+    //               [--palette <colorblind|high-contrast>] [--glyphs] [--large-text]
+    //               [--keys <file>] [--question-marks] [--practice]
+    //               [--preset <beginner|intermediate|expert>] [--seed <n>]
+    //               [--grid <rect|torus|tri|cube|endless>] [--depth <n>]
+    //               [--neighbours <standard|cross|knight|d_row,d_col;...>] [--multi-mines]
+    //               [--lives <n>] [--time-attack <minutes>] [--survival <seconds per row>]
+    //               [--daily [--player <name>]] [--daily-export <file>] [--daily-merge <file>]
+    //               [--record <file>] [--layout <file>] [--export-layout <file>]
+    //             | [--replay <file>]
+    let args: Vec<String> = std::env::args().collect();
+    let arg_value = |name: &str| {
+        args.iter()
+            .position(|x| x == name)
+            .and_then(|i| args.get(i + 1))
+    };
+
+    let theme_name = arg_value("--theme").map_or("classic", String::as_str);
+    let theme = Theme::load(theme_name).unwrap_or_else(|e| {
+        eprintln!("can't load theme {}: {}", theme_name, e);
+        Theme::default()
+    });
+    let accessibility = Accessibility {
+        palette: match arg_value("--palette").map(String::as_str) {
+            Some("colorblind") => Palette::ColorblindSafe,
+            Some("high-contrast") => Palette::HighContrast,
+            Some(palette) => panic!("unknown palette: {}", palette),
+            None => Palette::Theme,
         },
-        mines_cnt: 99,
+        glyphs: args.iter().any(|x| x == "--glyphs"),
+        large_text: args.iter().any(|x| x == "--large-text"),
     };
+
+    if let Some(path) = arg_value("--replay") {
+        let replay = Replay::load(path).expect("can't load replay");
+        let mut client = Client::new(&replay.params);
+        client.set_theme(theme);
+        client.set_accessibility(accessibility);
+        client.playback(replay).await;
+    }
+
+    let mut params = match arg_value("--preset") {
+        Some(name) => InitParams::preset(name).expect("unknown preset"),
+        None => InitParams {
+            grid_type: GridType::RectGrid {
+                heigth: 20,
+                width: 30,
+            },
+            mines_cnt: 99,
+            seed: None,
+            practice: false,
+            neighbourhood: Neighbourhood::Standard,
+            multi_mines: false,
+            lives: 1,
+            mode: GameMode::Classic,
+        },
+    };
+    params.practice = args.iter().any(|x| x == "--practice");
+    if let Some(neighbourhood) = arg_value("--neighbours") {
+        params.neighbourhood = neighbourhood.parse().expect("unknown neighbourhood");
+    }
+    if let Some(kind) = arg_value("--grid") {
+        // the presets are rect boards
+        let (heigth, width) = params.grid_type.size().unwrap_or((20, 30));
+        // layers of a cube
+        let depth =
+            arg_value("--depth").map_or(1, |depth| depth.parse().expect("depth must be a number"));
+        params.grid_type = GridType::from_kind(kind, heigth, width, depth).expect("unknown grid");
+        if let GridType::EndlessGrid = params.grid_type {
+            // the share of mines of the board, per chunk
+            params.mines_cnt = (params.mines_cnt * CHUNK_CELLS / (heigth * width))
+                .clamp(params.neighbourhood.min_chunk_mines(), MAX_CHUNK_MINES);
+        }
+    }
+    params.multi_mines = args.iter().any(|x| x == "--multi-mines");
+    if let Some(lives) = arg_value("--lives") {
+        params.lives = lives
+            .parse()
+            .ok()
+            .filter(|&lives| lives > 0)
+            .expect("lives must be a positive number");
+    }
+    if let Some(minutes) = arg_value("--time-attack") {
+        let minutes: u64 = minutes.parse().expect("time limit must be a number");
+        params.mode = GameMode::TimeAttack {
+            limit: Duration::from_secs(minutes * 60),
+        };
+    }
+    if let Some(secs) = arg_value("--survival") {
+        let secs = secs
+            .parse()
+            .ok()
+            .filter(|&secs| secs > 0)
+            .expect("row period must be a positive number");
+        params.mode = GameMode::Survival {
+            period: Duration::from_secs(secs),
+        };
+    }
+    if params.mode != GameMode::Classic
+        && (arg_value("--record").is_some() || arg_value("--layout").is_some())
+    {
+        panic!("time attack and survival games can't be recorded or laid out");
+    }
+    if let Some(seed) = arg_value("--seed") {
+        params.seed = Some(seed.parse().expect("seed must be a number"));
+    }
+    if let Err(e) = params.validate() {
+        panic!("{}", e);
+    }
+    // the same board for everyone playing the preset on the day
+    let today = Date::today();
+    let daily = args.iter().any(|x| x == "--daily");
+    if daily {
+        if arg_value("--seed").is_some()
+            || arg_value("--layout").is_some()
+            || params.mode != GameMode::Classic
+        {
+            panic!("a daily board can't have a seed, a layout or a game mode");
+        }
+        params.seed = Some(daily_seed(today, &params.preset_name()));
+    }
+    // the daily results are exported or merged into a shared leaderboard without playing
+    if let Some(path) = arg_value("--daily-export") {
+        DailyResults::load(DAILY_PATH)
+            .and_then(|results| results.save(path))
+            .expect("can't export daily results");
+        return;
+    }
+    if let Some(path) = arg_value("--daily-merge") {
+        let mut leaderboard = DailyResults::load(path).expect("can't load leaderboard");
+        let added =
+            leaderboard.merge(DailyResults::load(DAILY_PATH).expect("can't load daily results"));
+        leaderboard.save(path).expect("can't save leaderboard");
+        let preset = params.preset_name();
+        println!("{} results merged, {} daily {}:", added, preset, today);
+        for (i, result) in leaderboard.leaderboard(today, &preset).iter().enumerate() {
+            println!(
+                "{:3}. {} {} {:.2}s",
+                i + 1,
+                result.player,
+                if result.won { "won" } else { "lost" },
+                result.time.as_secs_f32()
+            );
+        }
+        return;
+    }
     let mut server = Server::new();
-    server.new_game(&params);
+    server.set_practice(params.practice);
+    server.set_lives(params.lives);
+
+    let autosave = Path::new(AUTOSAVE_PATH);
+    if let Some(path) = arg_value("--layout") {
+        server
+            .load_layout(Path::new(path))
+            .expect("can't load board layout");
+    } else if autosave.exists() && args.iter().any(|x| BOARD_ARGS.contains(&x.as_str())) {
+        eprintln!(
+            "board options given, the game interrupted in {} isn't resumed",
+            AUTOSAVE_PATH
+        );
+    } else if autosave.exists() {
+        // resume the game interrupted by closing the window
+        if let Err(e) = server.load_game(autosave) {
+            eprintln!("can't resume game: {}", e);
+        }
+        if let Err(e) = std::fs::remove_file(autosave) {
+            eprintln!("can't remove {}: {}", AUTOSAVE_PATH, e);
+        }
+    }
+    if !server.is_in_game() {
+        server.new_game(&params);
+    }
+    if let Some(path) = arg_value("--export-layout") {
+        server
+            .export_layout(Path::new(path))
+            .expect("can't export board layout");
+    }
+
+    let mut client = Client::new(&server.init_params().unwrap());
+    client.set_theme(theme);
+    client.set_accessibility(accessibility);
+    if let Some(path) = arg_value("--keys") {
+        client.set_key_bindings(KeyBindings::load(path).expect("can't load key bindings"));
+    }
+    client.set_question_marks(args.iter().any(|x| x == "--question-marks"));
+    if daily {
+        let player = arg_value("--player")
+            .cloned()
+            .or_else(|| std::env::var("USER").ok())
+            .unwrap_or_else(|| "player".to_string());
+        // a word of the results file
+        let player = player.split_whitespace().collect::<Vec<_>>().join("_");
+        client.set_daily(today, player);
+    }
+    // a resumed game starts with some cells already opened
+    client.process_server_data(server.game_view());
+
+    if let Some(path) = arg_value("--record") {
+        let mut recorder = Recorder::create(path, &server.init_params().unwrap(), &server.mines())
+            .expect("can't create replay file");
+        // a resumed game starts with some cells already opened
+        recorder
+            .record_response(&server.game_view())
+            .expect("can't write replay file");
+        client.record_to(recorder);
+    }
 
-    let mut client = Client::new(&params);
-    // let mut client_field = init_client_field(&params);
+    // the game is played in a room of a lobby of its own
+    let mut lobby = Lobby::local();
+    let player = lobby.connect();
+    lobby.open_room(player, "local".to_string(), server);
+    client
+        .run(|msg| {
+            let responses = match msg {
+                Some(msg) => lobby.process_request(player, LobbyRequest::Game(msg)),
+                None => lobby.tick(),
+            };
+            local_messages(player, responses)
+        })
+        .await;
 
-    client.run(|cells| server.process_client_data(cells)).await;
+    // time attack and survival games aren't saved
+    match lobby.server(player) {
+        Some(server) if server.is_in_game() && params.mode == GameMode::Classic => {
+            if let Err(e) = server.save_game(autosave) {
+                eprintln!("can't save game: {}", e);
+            }
+        }
+        _ => {}
+    }
 }
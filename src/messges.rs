@@ -1,9 +1,109 @@
 use crate::common::*;
+use std::path::PathBuf;
+use std::time::Duration;
 
-pub struct OpenCellsRq {
-    crds: Vec<Coords>,
+pub enum ClientMessage {
+    OpenCells(Vec<Coords>),
+    // see is_flag_weight, 0 takes the flag off
+    SetFlag { crds: Coords, flag: i8 },
+    SaveGame(PathBuf),
+    LoadGame(PathBuf),
+    // practice games only
+    Undo,
+    // endless boards only: the chunks in the window, the server sends the cells of these only
+    ShowChunks(Vec<(i64, i64)>),
 }
 
-pub struct OpenCellsRs {
-    crds: Vec<Cell>,
+// see ServerMessage::Mode
+#[derive(Copy, Clone)]
+pub enum ModeStatus {
+    TimeAttack {
+        boards_cleared: usize,
+        time_left: Duration,
+    },
+    Survival {
+        rows_added: usize,
+        next_row_in: Duration,
+    },
+}
+
+#[derive(Clone)]
+pub enum ServerMessage {
+    NewGame(InitParams),
+    Cells(Vec<Cell>),
+    // the cells with their flags
+    Marked(Vec<(Coords, i8)>),
+    GameOver {
+        won: bool,
+        elapsed: Duration,
+        stats: BoardStats,
+        // the whole layout, so the client can show what it missed,
+        // the mines of the shown chunks of an endless board
+        mines: Vec<Cell>,
+        // safe cells opened, the score of an endless game
+        cleared: usize,
+    },
+    // lives left, see InitParams::lives, after a mine is blown, an undo and in the game view
+    Lives(usize),
+    // pushed by the server every second of a time attack or a survival game
+    Mode(ModeStatus),
+    // a survival board got a new row, its cells and flags follow
+    Grown(InitParams),
+    // the last move was taken back, the whole view follows
+    Undone,
+    Error(String),
+}
+
+pub type ClientId = usize;
+pub type RoomId = usize;
+
+// requests to the lobby of the game server, a client is in one room at most
+pub enum LobbyRequest {
+    ListRooms,
+    // the creator joins the room as its host
+    CreateRoom {
+        name: String,
+        init_params: InitParams,
+    },
+    JoinRoom(RoomId),
+    // joins to watch the game, a spectator has no ready check and makes no moves,
+    // the players of a room share one board, there's no race mode of a board per player
+    // to switch between yet
+    Spectate(RoomId),
+    LeaveRoom,
+    // the ready check, every member has to pass it before a game starts
+    SetReady(bool),
+    // the host only
+    SetParams(InitParams),
+    Kick(ClientId),
+    StartGame,
+    // a move on the board of the room, shared by its members
+    Game(ClientMessage),
+}
+
+pub struct RoomInfo {
+    pub id: RoomId,
+    pub name: String,
+    pub preset: String,
+    pub members_cnt: usize,
+    pub spectators_cnt: usize,
+    pub in_game: bool,
+}
+
+pub enum LobbyResponse {
+    Rooms(Vec<RoomInfo>),
+    // sent to every member and spectator when someone joins, leaves or changes the room,
+    // the members in the order they joined with their ready checks, the first one is the host
+    Room {
+        id: RoomId,
+        name: String,
+        init_params: InitParams,
+        members: Vec<(ClientId, bool)>,
+        spectators: Vec<ClientId>,
+    },
+    // left the room or kicked out of it
+    Left,
+    // the messages of the room's game, sent to every member and spectator
+    Game(Vec<ServerMessage>),
+    Error(String),
 }
@@ -0,0 +1,259 @@
+use crate::common::*;
+use crate::messges::*;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::time::Instant;
+
+// replay file layout:
+//   rs-minesweeper replay v1
+//   <rect|torus|tri> <heigth> <width> <mines_cnt>
+//     | cube <heigth> <width> <depth> <mines_cnt> | endless <mines_cnt per chunk>
+//     followed by the neighbourhood of a rect, torus or endless board unless it's the standard one
+//   mines <row,col> <row,col:state> ..., the state of the cells of a single mine left out
+//   <ms> open <row,col> ...
+//   <ms> flag <row,col> <0|1|2|3|-1>
+//   <ms> cells <row,col:state> ...
+//   <ms> over <won|lost> <row,col> <row,col:state> ..., the mines of the game over like the ones above,
+//     the mines line of an endless board is empty, its mines are the ones of the chunks shown
+//   <ms> undo, every cell closed again, the cells and flags left follow
+const REPLAY_HEADER: &str = "rs-minesweeper replay v1";
+
+pub enum ReplayEvent {
+    Open(Vec<Coords>),
+    // see is_flag_weight, 0 takes the flag off
+    Flag(Coords, i8),
+    Update(Vec<Cell>),
+    // won, the mines of the game over, the ones of the mines line if none were recorded
+    Over(bool, Vec<Cell>),
+    Undo,
+}
+
+pub struct Recorder {
+    out: BufWriter<File>,
+    started: Instant,
+}
+
+impl Recorder {
+    pub fn create<P: AsRef<Path>>(
+        path: P,
+        params: &InitParams,
+        mines: &[Cell],
+    ) -> io::Result<Recorder> {
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(out, "{}", REPLAY_HEADER)?;
+        match params.grid_type {
+            GridType::RectGrid { heigth, width }
+            | GridType::TorusGrid { heigth, width }
+            | GridType::TriGrid { heigth, width } => write!(
+                out,
+                "{} {} {} {}",
+                params.grid_type.kind(),
+                heigth,
+                width,
+                params.mines_cnt
+            )?,
+            GridType::CubeGrid {
+                heigth,
+                width,
+                depth,
+            } => write!(
+                out,
+                "cube {} {} {} {}",
+                heigth, width, depth, params.mines_cnt
+            )?,
+            GridType::EndlessGrid => write!(out, "endless {}", params.mines_cnt)?,
+            GridType::HexGrid => std::unimplemented!(),
+        }
+        if params.neighbourhood != Neighbourhood::Standard {
+            write!(out, " {}", params.neighbourhood)?;
+        }
+        writeln!(out)?;
+        write!(out, "mines")?;
+        write_mines(&mut out, mines)?;
+        writeln!(out)?;
+        out.flush()?;
+        Ok(Recorder {
+            out,
+            started: Instant::now(),
+        })
+    }
+
+    pub fn record_request(&mut self, request: &ClientMessage) -> io::Result<()> {
+        let ms = self.started.elapsed().as_millis();
+        match request {
+            ClientMessage::OpenCells(coords) => {
+                write!(self.out, "{} open", ms)?;
+                for crds in coords {
+                    write!(self.out, " {}", crds)?;
+                }
+                writeln!(self.out)?;
+            }
+            ClientMessage::SetFlag { crds, flag } => {
+                writeln!(self.out, "{} flag {} {}", ms, crds, flag)?
+            }
+            _ => return Ok(()),
+        }
+        self.out.flush()
+    }
+
+    pub fn record_response(&mut self, response: &[ServerMessage]) -> io::Result<()> {
+        let ms = self.started.elapsed().as_millis();
+        for msg in response {
+            match msg {
+                ServerMessage::Cells(cells) => {
+                    write!(self.out, "{} cells", ms)?;
+                    for cell in cells {
+                        write!(self.out, " {}:{}", cell.crds, cell.state)?;
+                    }
+                    writeln!(self.out)?;
+                }
+                ServerMessage::Marked(flags) => {
+                    for (crds, flag) in flags {
+                        writeln!(self.out, "{} flag {} {}", ms, crds, flag)?;
+                    }
+                }
+                ServerMessage::Undone => writeln!(self.out, "{} undo", ms)?,
+                ServerMessage::GameOver { won, mines, .. } => {
+                    write!(
+                        self.out,
+                        "{} over {}",
+                        ms,
+                        if *won { "won" } else { "lost" }
+                    )?;
+                    write_mines(&mut self.out, mines)?;
+                    writeln!(self.out)?;
+                }
+                _ => {}
+            }
+        }
+        self.out.flush()
+    }
+}
+
+// the cells of a single mine are written without their state
+fn write_mines(out: &mut impl Write, mines: &[Cell]) -> io::Result<()> {
+    for cell in mines {
+        match cell.state {
+            CellState::Mine(1) => write!(out, " {}", cell.crds)?,
+            state => write!(out, " {}:{}", cell.crds, state)?,
+        }
+    }
+    Ok(())
+}
+
+pub struct Replay {
+    pub params: InitParams,
+    pub mines: Vec<Cell>,
+    // (ms since the start of the game, event)
+    pub events: Vec<(u64, ReplayEvent)>,
+}
+
+impl Replay {
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Replay> {
+        let mut lines = BufReader::new(File::open(path)?).lines();
+        if next_line(&mut lines)? != REPLAY_HEADER {
+            return Err(invalid_data("not a replay file"));
+        }
+
+        let line = next_line(&mut lines)?;
+        let mut size: Vec<&str> = line.split_whitespace().collect();
+        let neighbourhood = header_neighbourhood(&mut size).map_err(invalid_data)?;
+        let parse = |x: &str| {
+            x.parse::<usize>()
+                .map_err(|_| invalid_data(format!("bad board size: {}", line)))
+        };
+        // only cubes have a depth and endless boards have no size
+        let (kind, heigth, width, depth, mines_cnt) = match size.as_slice() {
+            [kind @ "endless", mines_cnt] => (*kind, "0", "0", "1", mines_cnt),
+            [kind, heigth, width, mines_cnt] => (*kind, *heigth, *width, "1", mines_cnt),
+            [kind, heigth, width, depth, mines_cnt] => (*kind, *heigth, *width, *depth, mines_cnt),
+            _ => return Err(invalid_data(format!("bad board size: {}", line))),
+        };
+        let mut params = InitParams {
+            grid_type: GridType::from_kind(kind, parse(heigth)?, parse(width)?, parse(depth)?)
+                .ok_or_else(|| invalid_data(format!("bad board size: {}", line)))?,
+            mines_cnt: parse(mines_cnt)?,
+            seed: None,
+            practice: false,
+            neighbourhood,
+            // told by the mines
+            multi_mines: false,
+            // the blown mines are in the cells, the lives left aren't recorded
+            lives: 1,
+            // games of other modes span several boards and aren't recorded
+            mode: GameMode::Classic,
+        };
+        let grid_type = params.grid_type.clone();
+        let parse_coords = |x: &str| grid_type.parse_coords(x).map_err(invalid_data);
+        let parse_cell = |x: &str| {
+            let (crds, state) = x
+                .split_once(':')
+                .ok_or_else(|| invalid_data(format!("bad cell: {}", x)))?;
+            Ok(Cell {
+                crds: parse_coords(crds)?,
+                state: state.parse().map_err(invalid_data)?,
+            })
+        };
+
+        let line = next_line(&mut lines)?;
+        let mut mines_line = line.split_whitespace();
+        if mines_line.next() != Some("mines") {
+            return Err(invalid_data(format!("bad mines line: {}", line)));
+        }
+        let parse_mine = |x: &str| match x.contains(':') {
+            true => parse_cell(x),
+            false => Ok(Cell {
+                crds: parse_coords(x)?,
+                state: CellState::Mine(1),
+            }),
+        };
+        let mines = mines_line.map(parse_mine).collect::<io::Result<Vec<_>>>()?;
+        params.multi_mines = mines.iter().any(|cell| cell.state != CellState::Mine(1));
+        // the client is set up for the params, it can't take a board of no cells
+        params.validate().map_err(invalid_data)?;
+
+        let mut events = Vec::new();
+        for line in lines {
+            let line = line?;
+            let mut words = line.split_whitespace();
+            let ms = match words.next() {
+                Some(x) => x
+                    .parse::<u64>()
+                    .map_err(|_| invalid_data(format!("bad timestamp: {}", line)))?,
+                None => continue,
+            };
+            let event = match words.next() {
+                Some("open") => {
+                    ReplayEvent::Open(words.map(parse_coords).collect::<io::Result<_>>()?)
+                }
+                Some("flag") => match (words.next(), words.next().map(str::parse::<i8>)) {
+                    (Some(crds), Some(Ok(flag))) if flag == 0 || is_flag_weight(flag) => {
+                        ReplayEvent::Flag(parse_coords(crds)?, flag)
+                    }
+                    _ => return Err(invalid_data(format!("bad flag: {}", line))),
+                },
+                Some("cells") => {
+                    ReplayEvent::Update(words.map(parse_cell).collect::<io::Result<_>>()?)
+                }
+                Some("undo") => ReplayEvent::Undo,
+                Some("over") => {
+                    let won = match words.next() {
+                        Some("won") => true,
+                        Some("lost") => false,
+                        _ => return Err(invalid_data(format!("bad game over: {}", line))),
+                    };
+                    ReplayEvent::Over(won, words.map(parse_mine).collect::<io::Result<_>>()?)
+                }
+                _ => return Err(invalid_data(format!("bad event: {}", line))),
+            };
+            events.push((ms, event));
+        }
+
+        Ok(Replay {
+            params,
+            mines,
+            events,
+        })
+    }
+}
@@ -1,171 +1,1812 @@
 use crate::common::*;
-use rand::Rng;
+use crate::messges::*;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+// board layout file, e.g. for hand-crafted test boards:
+//   # comment lines before the header are skipped
+//   <rect|torus|tri> <heigth> <width> | cube <heigth> <width> <depth>
+//     followed by the neighbourhood of a rect or torus board unless it's the standard one
+//   <one line per row, '*' - mine, '2' and '3' - cells of several mines, '-' - anti-mine,
+//    '.' - safe, the layers of a cube one after another>
+//
+// save file layout:
+//   rs-minesweeper save v1
+//   state <in_game|won|lost>
+//   elapsed <ms>
+//   lives <left> <of>, a classic game of one life if missing
+//   practice, in practice games only, the moves before the save can't be undone
+//   seed <n|->
+//   <board layout>
+//   view
+//   <one line per row, '#' - closed, 'F' - flag, 'D' and 'T' - flags of 2 and 3 mines,
+//    'A' - anti-mine flag, '*' - blown mine,
+//    '0'..'9' and 'a'..'z' for 10..35, '-' for less than 0 and '+' for more - opened>
+// an endless board is saved as:
+//   seed <n>
+//   endless <mines_cnt per chunk> [neighbourhood]
+//   opened <row,col> <row,col> ...
+//   flags <row,col> <row,col> ...
+const SAVE_HEADER: &str = "rs-minesweeper save v1";
 
 enum GameState {
-    InGame { field: Box<dyn TServerField> },
+    InGame {
+        field: Box<dyn TServerField>,
+    },
     NotInGame,
-    GameOver,
+    GameOver {
+        field: Box<dyn TServerField>,
+        won: bool,
+    },
+}
+
+#[derive(Default)]
+struct GameTimer {
+    elapsed: Duration,
+    running_since: Option<Instant>,
+}
+
+impl GameTimer {
+    fn start(&mut self) {
+        if self.running_since.is_none() {
+            self.running_since = Some(Instant::now());
+        }
+    }
+
+    fn stop(&mut self) {
+        if let Some(since) = self.running_since.take() {
+            self.elapsed += since.elapsed();
+        }
+    }
+
+    fn elapsed(&self) -> Duration {
+        match self.running_since {
+            Some(since) => self.elapsed + since.elapsed(),
+            None => self.elapsed,
+        }
+    }
+}
+
+// a practice game move that can be undone
+enum Move {
+    // opened cells, the flags opening them removed and the lives it cost
+    Open {
+        cells: Vec<Coords>,
+        flags: Vec<(Coords, i8)>,
+        blown: usize,
+    },
+    // the flag that was replaced
+    Flag(Coords, i8),
 }
 
 pub struct Server {
     game_state: GameState,
+    timer: GameTimer,
+    // moves of the current game, kept in practice mode only
+    history: Option<Vec<Move>>,
+    // see InitParams::lives
+    lives: usize,
+    lives_left: usize,
+    // see InitParams::mode
+    mode: GameMode,
+    // the time attack clock, running over all the boards from the first open
+    session: GameTimer,
+    // boards cleared in a time attack, rows added in a survival game
+    mode_score: usize,
+    // the game time the survival board last grew at
+    grown_at: Duration,
+    // whole seconds of the last ModeStatus pushed
+    pushed_secs: Option<u64>,
     // field: Box<dyn TServerField>,
     // clients: Vec<u32>
 }
 
+impl Default for Server {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Server {
     pub fn new() -> Server {
         Server {
             game_state: GameState::NotInGame,
+            timer: GameTimer::default(),
+            history: None,
+            lives: 1,
+            lives_left: 1,
+            mode: GameMode::Classic,
+            session: GameTimer::default(),
+            mode_score: 0,
+            grown_at: Duration::ZERO,
+            pushed_secs: None,
         }
     }
 
+    // stays on for the games loaded later
+    pub fn set_practice(&mut self, practice: bool) {
+        self.history = if practice { Some(Vec::new()) } else { None };
+    }
+
+    // kept for the boards loaded from layouts later, saved games have lives of their own
+    pub fn set_lives(&mut self, lives: usize) {
+        self.lives = lives;
+        self.lives_left = lives;
+    }
+
     pub fn new_game(&mut self, init_params: &InitParams) {
-        match init_params.grid_type {
-            GridType::RectGrid { heigth, width } => {
-                self.game_state = GameState::InGame {
-                    field: Box::new(RectServerField::new(heigth, width, init_params.mines_cnt)),
+        self.start_board(init_params);
+        self.set_mode(init_params.mode);
+    }
+
+    fn set_mode(&mut self, mode: GameMode) {
+        self.mode = mode;
+        self.session = GameTimer::default();
+        self.mode_score = 0;
+        self.pushed_secs = None;
+    }
+
+    // a board of the current game, the next one of a time attack
+    fn start_board(&mut self, init_params: &InitParams) {
+        let seed = init_params
+            .seed
+            .unwrap_or_else(|| ::rand::thread_rng().gen());
+        let field: Box<dyn TServerField> =
+            match init_params.grid_type.topology(&init_params.neighbourhood) {
+                Some(topology) => Box::new(GridServerField::new(init_params, topology, seed)),
+                None => Box::new(EndlessServerField::new(
+                    init_params.neighbourhood.clone(),
+                    init_params.mines_cnt,
+                    seed,
+                )),
+            };
+        self.game_state = GameState::InGame { field };
+        self.timer = GameTimer::default();
+        self.grown_at = Duration::ZERO;
+        self.set_practice(init_params.practice);
+        self.set_lives(init_params.lives);
+    }
+
+    // the messages the server pushes on its own, polled every frame
+    pub fn tick(&mut self) -> Vec<ServerMessage> {
+        if !self.is_in_game() {
+            return vec![];
+        }
+        let mut res = Vec::new();
+        match self.mode {
+            GameMode::Classic => return res,
+            GameMode::TimeAttack { limit } if self.session.elapsed() >= limit => {
+                self.timer.stop();
+                self.session.stop();
+                if let GameState::InGame { field } =
+                    std::mem::replace(&mut self.game_state, GameState::NotInGame)
+                {
+                    self.game_state = GameState::GameOver { field, won: true };
+                }
+                res.extend(self.game_over_message());
+            }
+            GameMode::Survival { period } if self.timer.elapsed() >= self.grown_at + period => {
+                self.grown_at += period;
+                let grown = match &mut self.game_state {
+                    GameState::InGame { field } => field.add_row(),
+                    _ => false,
+                };
+                if grown {
+                    self.mode_score += 1;
+                    res.push(ServerMessage::Grown(self.init_params().unwrap()));
+                    if let GameState::InGame { field } = &self.game_state {
+                        res.push(ServerMessage::Cells(field.revealed_cells()));
+                        res.push(ServerMessage::Marked(field.flags()));
+                    }
+                }
+            }
+            _ => {}
+        }
+        let status = self.mode_status().unwrap();
+        let secs = match status {
+            ModeStatus::TimeAttack { time_left, .. } => time_left.as_secs(),
+            ModeStatus::Survival { next_row_in, .. } => next_row_in.as_secs(),
+        };
+        if !res.is_empty() || self.pushed_secs != Some(secs) {
+            self.pushed_secs = Some(secs);
+            res.push(ServerMessage::Mode(status));
+        }
+        res
+    }
+
+    fn mode_status(&self) -> Option<ModeStatus> {
+        match self.mode {
+            GameMode::Classic => None,
+            GameMode::TimeAttack { limit } => Some(ModeStatus::TimeAttack {
+                boards_cleared: self.mode_score,
+                time_left: limit.saturating_sub(self.session.elapsed()),
+            }),
+            GameMode::Survival { period } => Some(ModeStatus::Survival {
+                rows_added: self.mode_score,
+                next_row_in: (self.grown_at + period).saturating_sub(self.timer.elapsed()),
+            }),
+        }
+    }
+
+    // seeded after the last board, so a seeded time attack can be played again
+    fn next_board(&mut self) -> Vec<ServerMessage> {
+        let params = self.init_params().unwrap();
+        self.start_board(&InitParams {
+            seed: params.seed.map(|seed| seed.wrapping_add(1)),
+            ..params
+        });
+        self.game_view()
+    }
+
+    pub fn is_in_game(&self) -> bool {
+        matches!(self.game_state, GameState::InGame { .. })
+    }
+
+    // a read-only client gets the game view and the pushed messages like a player does,
+    // the mines only come with the game over, any move or file request is rejected
+    pub fn process_spectator_data(&mut self, client_package: ClientMessage) -> Vec<ServerMessage> {
+        match client_package {
+            ClientMessage::ShowChunks(_) => self.process_client_data(client_package),
+            _ => vec![ServerMessage::Error(
+                "spectators can't make moves".to_string(),
+            )],
+        }
+    }
+
+    pub fn process_client_data(&mut self, client_package: ClientMessage) -> Vec<ServerMessage> {
+        match client_package {
+            ClientMessage::OpenCells(coords) => self.open_cells(coords),
+            ClientMessage::SetFlag { crds, flag } => {
+                if let GameState::InGame { field } = &mut self.game_state {
+                    let replaced = field.flag(crds);
+                    field.set_flag(crds, flag);
+                    if let Some(history) = &mut self.history {
+                        history.push(Move::Flag(crds, replaced));
+                    }
                 }
+                vec![]
             }
-            GridType::HexGrid => std::unimplemented!(),
+            ClientMessage::Undo => self.undo(),
+            ClientMessage::ShowChunks(chunks) => match &mut self.game_state {
+                GameState::InGame { field } | GameState::GameOver { field, .. } => {
+                    let (cells, flags) = field.show_chunks(chunks);
+                    let mut res = vec![ServerMessage::Cells(cells), ServerMessage::Marked(flags)];
+                    // the mines of the newly shown chunks
+                    res.extend(self.game_over_message());
+                    res
+                }
+                GameState::NotInGame => vec![],
+            },
+            ClientMessage::SaveGame(path) => match self.save_game(&path) {
+                Ok(()) => vec![],
+                Err(e) => vec![ServerMessage::Error(format!("can't save game: {}", e))],
+            },
+            ClientMessage::LoadGame(path) => match self.load_game(&path) {
+                Ok(()) => self.game_view(),
+                Err(e) => vec![ServerMessage::Error(format!("can't load game: {}", e))],
+            },
+        }
+    }
+
+    pub fn init_params(&self) -> Option<InitParams> {
+        match &self.game_state {
+            GameState::InGame { field } | GameState::GameOver { field, .. } => Some(InitParams {
+                practice: self.history.is_some(),
+                lives: self.lives,
+                mode: self.mode,
+                ..field.init_params()
+            }),
+            _ => None,
+        }
+    }
+
+    // the flag on a cell of the current game, 0 for none
+    pub fn flag(&self, crds: Coords) -> i8 {
+        match &self.game_state {
+            GameState::InGame { field } | GameState::GameOver { field, .. } => field.flag(crds),
+            _ => 0,
         }
     }
 
-    pub fn process_client_data(&mut self, client_package: Vec<Coords>) -> Vec<Cell> {
+    pub fn mines(&self) -> Vec<Cell> {
         match &self.game_state {
-            GameState::InGame { field } => field.open_cells(client_package),
+            GameState::InGame { field } | GameState::GameOver { field, .. } => field.mines(),
             _ => {
                 vec![]
             }
         }
     }
+
+    pub fn board_stats(&self) -> Option<BoardStats> {
+        match &self.game_state {
+            GameState::InGame { field } | GameState::GameOver { field, .. } => {
+                Some(field.board_stats())
+            }
+            _ => None,
+        }
+    }
+
+    // everything a freshly connected client needs to draw the current game
+    pub fn game_view(&self) -> Vec<ServerMessage> {
+        let mut res = match &self.game_state {
+            GameState::InGame { field } | GameState::GameOver { field, .. } => vec![
+                ServerMessage::NewGame(self.init_params().unwrap()),
+                ServerMessage::Cells(field.revealed_cells()),
+                ServerMessage::Marked(field.flags()),
+                ServerMessage::Lives(self.lives_left),
+            ],
+            _ => {
+                vec![]
+            }
+        };
+        res.extend(self.mode_status().map(ServerMessage::Mode));
+        res.extend(self.game_over_message());
+        res
+    }
+
+    fn game_over_message(&self) -> Option<ServerMessage> {
+        match &self.game_state {
+            GameState::GameOver { field, won } => Some(ServerMessage::GameOver {
+                won: *won,
+                elapsed: self.timer.elapsed(),
+                stats: field.board_stats(),
+                mines: field.mines(),
+                cleared: field.opened_cnt(),
+            }),
+            _ => None,
+        }
+    }
+
+    fn open_cells(&mut self, coords: Vec<Coords>) -> Vec<ServerMessage> {
+        let field = match &mut self.game_state {
+            GameState::InGame { field } => field,
+            _ => return vec![],
+        };
+        self.timer.start();
+        self.session.start();
+        let flags = match self.history {
+            Some(_) => field.flags(),
+            None => vec![],
+        };
+        let cells = field.open_cells(coords);
+        let blown = cells.iter().filter(|cell| cell.state.is_mine()).count();
+        if let Some(history) = &mut self.history {
+            let opened: HashSet<Coords> = cells.iter().map(|cell| cell.crds).collect();
+            history.push(Move::Open {
+                cells: opened.iter().copied().collect(),
+                flags: flags
+                    .into_iter()
+                    .filter(|(crds, _)| opened.contains(crds))
+                    .collect(),
+                blown,
+            });
+        }
+        // every blown mine costs a life, the game goes on while there are any left
+        self.lives_left = self.lives_left.saturating_sub(blown);
+        let lost = self.lives_left == 0;
+        let cells: Vec<Cell> = cells
+            .into_iter()
+            .filter(|cell| field.is_shown(cell.crds))
+            .collect();
+        let cleared = field.is_cleared() && !matches!(self.mode, GameMode::Survival { .. });
+        if (lost || cleared) && matches!(self.mode, GameMode::TimeAttack { .. }) {
+            if !lost {
+                self.mode_score += 1;
+            }
+            let mut res = vec![ServerMessage::Cells(cells)];
+            res.extend(self.next_board());
+            return res;
+        }
+        if lost || cleared {
+            self.timer.stop();
+            if let GameState::InGame { field } =
+                std::mem::replace(&mut self.game_state, GameState::NotInGame)
+            {
+                self.game_state = GameState::GameOver { field, won: !lost };
+            }
+        }
+        let mut res = vec![ServerMessage::Cells(cells)];
+        if blown > 0 {
+            res.push(ServerMessage::Lives(self.lives_left));
+        }
+        res.extend(self.game_over_message());
+        res
+    }
+
+    // takes back the last move, even the one that blew a mine
+    fn undo(&mut self) -> Vec<ServerMessage> {
+        let history = match &mut self.history {
+            Some(history) => history,
+            None => {
+                return vec![ServerMessage::Error(
+                    "undo is allowed in practice games only".to_string(),
+                )]
+            }
+        };
+        let last_move = match history.pop() {
+            Some(last_move) => last_move,
+            None => return vec![],
+        };
+        let mut field = match std::mem::replace(&mut self.game_state, GameState::NotInGame) {
+            GameState::InGame { field } | GameState::GameOver { field, .. } => field,
+            GameState::NotInGame => return vec![],
+        };
+        match last_move {
+            Move::Open {
+                cells,
+                flags,
+                blown,
+            } => {
+                self.lives_left = usize::min(self.lives_left + blown, self.lives);
+                field.close_cells(&cells);
+                for (crds, flag) in flags {
+                    field.set_flag(crds, flag);
+                }
+            }
+            Move::Flag(crds, flag) => field.set_flag(crds, flag),
+        }
+        let res = vec![
+            ServerMessage::Undone,
+            ServerMessage::Cells(field.revealed_cells()),
+            ServerMessage::Marked(field.flags()),
+            ServerMessage::Lives(self.lives_left),
+        ];
+        self.game_state = GameState::InGame { field };
+        res
+    }
+
+    pub fn save_game(&self, path: &Path) -> io::Result<()> {
+        let (field, state) = match &self.game_state {
+            GameState::InGame { field } => (field, "in_game"),
+            GameState::GameOver { field, won: true } => (field, "won"),
+            GameState::GameOver { field, won: false } => (field, "lost"),
+            GameState::NotInGame => return Err(io::Error::other("no game to save")),
+        };
+        if self.mode != GameMode::Classic {
+            return Err(io::Error::other(
+                "time attack and survival games can't be saved",
+            ));
+        }
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(out, "{}", SAVE_HEADER)?;
+        writeln!(out, "state {}", state)?;
+        writeln!(out, "elapsed {}", self.timer.elapsed().as_millis())?;
+        writeln!(out, "lives {} {}", self.lives_left, self.lives)?;
+        if self.history.is_some() {
+            writeln!(out, "practice")?;
+        }
+        field.write_state(&mut out)?;
+        out.flush()
+    }
+
+    pub fn load_layout(&mut self, path: &Path) -> io::Result<()> {
+        let mut lines = BufReader::new(File::open(path)?)
+            .lines()
+            .skip_while(|line| matches!(line, Ok(line) if line.starts_with('#')));
+        self.game_state = GameState::InGame {
+            field: Box::new(GridServerField::read_layout(&mut lines)?),
+        };
+        self.timer = GameTimer::default();
+        self.set_practice(self.history.is_some());
+        self.set_lives(self.lives);
+        self.set_mode(GameMode::Classic);
+        Ok(())
+    }
+
+    pub fn export_layout(&self, path: &Path) -> io::Result<()> {
+        let field = match &self.game_state {
+            GameState::InGame { field } | GameState::GameOver { field, .. } => field,
+            GameState::NotInGame => return Err(io::Error::other("no board to export")),
+        };
+        let mut out = BufWriter::new(File::create(path)?);
+        field.write_layout(&mut out)?;
+        out.flush()
+    }
+
+    pub fn load_game(&mut self, path: &Path) -> io::Result<()> {
+        let mut lines = BufReader::new(File::open(path)?).lines();
+        if next_line(&mut lines)? != SAVE_HEADER {
+            return Err(invalid_data("not a save file"));
+        }
+        let state = next_line(&mut lines)?;
+        let elapsed = next_line(&mut lines)?;
+        let elapsed = match elapsed.split_once(' ') {
+            Some(("elapsed", ms)) => Duration::from_millis(
+                ms.parse()
+                    .map_err(|_| invalid_data(format!("bad elapsed time: {}", elapsed)))?,
+            ),
+            _ => return Err(invalid_data(format!("bad elapsed time: {}", elapsed))),
+        };
+        let line = next_line(&mut lines)?;
+        let (lives_left, lives, practice, field) =
+            match line.split_whitespace().collect::<Vec<_>>()[..] {
+                ["lives", left, of] => {
+                    let parse = |x: &str| {
+                        x.parse::<usize>()
+                            .map_err(|_| invalid_data(format!("bad lives: {}", line)))
+                    };
+                    let (left, of) = (parse(left)?, parse(of)?);
+                    if of == 0 || left > of {
+                        return Err(invalid_data(format!("bad lives: {}", line)));
+                    }
+                    let line = next_line(&mut lines)?;
+                    if line == "practice" {
+                        (left, of, true, read_field_state(&mut lines)?)
+                    } else {
+                        let mut lines = std::iter::once(Ok(line)).chain(lines);
+                        (left, of, false, read_field_state(&mut lines)?)
+                    }
+                }
+                // the seed line of an older save
+                _ => (
+                    1,
+                    1,
+                    false,
+                    read_field_state(&mut std::iter::once(Ok(line.clone())).chain(lines))?,
+                ),
+            };
+
+        self.game_state = match state.as_str() {
+            "state in_game" => GameState::InGame { field },
+            "state won" => GameState::GameOver { field, won: true },
+            "state lost" => GameState::GameOver { field, won: false },
+            _ => return Err(invalid_data(format!("bad game state: {}", state))),
+        };
+        self.timer = GameTimer {
+            elapsed,
+            running_since: None,
+        };
+        // a practice game stays off the leaderboards in any session
+        self.set_practice(practice);
+        self.lives = lives;
+        self.lives_left = lives_left;
+        self.set_mode(GameMode::Classic);
+        Ok(())
+    }
 }
 
 trait TServerField {
-    fn open_cells(&self, coords: Vec<Coords>) -> Vec<Cell>;
+    fn open_cells(&mut self, coords: Vec<Coords>) -> Vec<Cell>;
+    // undoes open_cells
+    fn close_cells(&mut self, coords: &[Coords]);
+    // see is_flag_weight, 0 takes the flag off
+    fn set_flag(&mut self, crds: Coords, flag: i8);
+    fn flag(&self, crds: Coords) -> i8;
+    fn is_cleared(&self) -> bool;
+    fn mines(&self) -> Vec<Cell>;
+    fn board_stats(&self) -> BoardStats;
+    fn revealed_cells(&self) -> Vec<Cell>;
+    fn flags(&self) -> Vec<(Coords, i8)>;
+    fn init_params(&self) -> InitParams;
+    fn write_layout(&self, out: &mut dyn Write) -> io::Result<()>;
+    fn write_state(&self, out: &mut dyn Write) -> io::Result<()>;
+    // safe cells opened, the score of an endless game
+    fn opened_cnt(&self) -> usize;
+
+    // an endless board sends the client the cells of the chunks it shows only,
+    // returns the opened cells and the flags of the chunks that weren't shown before
+    fn show_chunks(&mut self, _chunks: Vec<(i64, i64)>) -> (Vec<Cell>, Vec<(Coords, i8)>) {
+        (vec![], vec![])
+    }
+
+    fn is_shown(&self, _crds: Coords) -> bool {
+        true
+    }
+
+    // survival: a row of new mines at the bottom, false for the boards that can't grow
+    fn add_row(&mut self) -> bool {
+        false
+    }
+}
+
+// a save file board of any grid
+fn read_field_state<I>(lines: &mut I) -> io::Result<Box<dyn TServerField>>
+where
+    I: Iterator<Item = io::Result<String>>,
+{
+    let line = next_line(lines)?;
+    let seed = match line.split_once(' ') {
+        Some(("seed", "-")) => None,
+        Some(("seed", seed)) => Some(
+            seed.parse()
+                .map_err(|_| invalid_data(format!("bad seed: {}", line)))?,
+        ),
+        _ => return Err(invalid_data(format!("bad seed: {}", line))),
+    };
+    let header = next_line(lines)?;
+    if header.starts_with("endless") {
+        Ok(Box::new(EndlessServerField::read_state(
+            seed, &header, lines,
+        )?))
+    } else {
+        Ok(Box::new(GridServerField::read_state(seed, &header, lines)?))
+    }
 }
 
-struct RectServerField {
-    heigth: usize,
-    width: usize,
+struct GridServerField {
+    // weighted, see InitParams::multi_mines
     mines_cnt: usize,
-    rows: Vec<Vec<CellState>>,
+    multi_mines: bool,
+    // per cell data, indexed by the topology
+    cells: Vec<CellState>,
+    revealed: Vec<bool>,
+    flags: Vec<i8>,
+    opened_cnt: usize,
+    // None for boards loaded from a layout
+    seed: Option<u64>,
+    // the rows of the board files
+    grid_type: GridType,
+    neighbourhood: Neighbourhood,
+    topology: Box<dyn Topology>,
 }
 
-impl RectServerField {
-    fn new(heigth: usize, width: usize, mut mines_cnt: usize) -> Self {
-        if heigth == 0 || width == 0 || heigth * width - 1 < mines_cnt {
+impl GridServerField {
+    // the topology is the one of the grid type of the params
+    fn new(init_params: &InitParams, topology: Box<dyn Topology>, seed: u64) -> Self {
+        let grid_type = init_params.grid_type.clone();
+        let (heigth, width) = topology.size();
+        let rows_cnt = heigth * grid_type.depth();
+        let cells_cnt = topology.cells_cnt();
+        let mut mines_cnt = init_params.mines_cnt;
+        if cells_cnt == 0 || cells_cnt - 1 < mines_cnt {
             panic!("invalid field params!")
         }
-        let mut f = RectServerField {
-            width,
-            heigth,
+        let mut f = GridServerField {
             mines_cnt,
-            rows: vec![vec![CellState::Empty(0); width]; heigth],
+            multi_mines: init_params.multi_mines,
+            cells: vec![CellState::Empty(0); cells_cnt],
+            revealed: vec![false; cells_cnt],
+            flags: vec![0; cells_cnt],
+            opened_cnt: 0,
+            seed: Some(seed),
+            grid_type,
+            neighbourhood: init_params.neighbourhood.clone(),
+            topology,
         };
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        // cells left without mines, one of them is kept safe
+        let mut free_cnt = cells_cnt;
         // todo quicker algo
         while mines_cnt > 0 {
-            let (r, c) = (
-                ::rand::thread_rng().gen_range(0..heigth),
-                ::rand::thread_rng().gen_range(0..width),
+            // drawn as a row of the board files and a col, as u32 rather than usize
+            // so a seed has the same layout on every platform, see InitParams::seed
+            let (row, col) = (
+                rng.gen_range(0..rows_cnt as u32) as usize,
+                rng.gen_range(0..width as u32) as usize,
             );
             // todo: 1 cell empty for multipleer or generate on first click for singleplayer
             // if center.is_some() && mine_coord == center.unwrap() {
             //     continue;
             // }
-            if f.rows[r][c] != CellState::Mine {
-                f.rows[r][c] = CellState::Mine;
-                mines_cnt -= 1;
+            let i = f.index(row, col);
+            if f.cells[i].is_mine() {
+                continue;
             }
+            f.cells[i] = if f.multi_mines {
+                random_weighted_mine(&mut rng, mines_cnt, free_cnt)
+            } else {
+                CellState::Mine(1)
+            };
+            // an anti-mine leaves one more mine to place
+            mines_cnt = (mines_cnt as isize - f.cells[i].weight() as isize) as usize;
+            free_cnt -= 1;
         }
-        // count empties
-        for r in 0..f.heigth {
-            for c in 0..width {
-                if f.rows[r][c] == CellState::Mine {
-                    continue;
-                }
-                let mut cnt = 0;
-                let min_r = if r == 0 { 0 } else { r - 1 };
-                let max_r = if r == f.heigth - 1 { f.heigth } else { r + 2 };
-                let min_c = if c == 0 { 0 } else { c - 1 };
-                let max_c = if c == f.width - 1 { f.width } else { c + 2 };
-                for rr in min_r..max_r {
-                    for cc in min_c..max_c {
-                        cnt += if f.rows[rr][cc] == CellState::Mine {
-                            1
-                        } else {
-                            0
-                        };
-                    }
+        f.count_empties();
+        f
+    }
+
+    // mines has a row for every row of the board files, the safe cells are Empty(0)
+    fn from_layout(
+        grid_type: GridType,
+        neighbourhood: Neighbourhood,
+        topology: Box<dyn Topology>,
+        mines: Vec<Vec<CellState>>,
+    ) -> Self {
+        let cells_cnt = topology.cells_cnt();
+        let weights = mines.iter().flatten().map(CellState::weight);
+        let mut f = GridServerField {
+            mines_cnt: weights.clone().map(|x| x as isize).sum::<isize>().max(0) as usize,
+            multi_mines: weights.into_iter().any(|x| x != 0 && x != 1),
+            cells: vec![CellState::Empty(0); cells_cnt],
+            revealed: vec![false; cells_cnt],
+            flags: vec![0; cells_cnt],
+            opened_cnt: 0,
+            seed: None,
+            grid_type,
+            neighbourhood,
+            topology,
+        };
+        for (row, cells) in mines.iter().enumerate() {
+            for (col, state) in cells.iter().enumerate() {
+                if state.is_mine() {
+                    let i = f.index(row, col);
+                    f.cells[i] = *state;
                 }
-                f.rows[r][c] = CellState::Empty(cnt);
             }
         }
+        f.count_empties();
         f
     }
 
-    fn fill_recursive(&self, crds: Coords, filled: &mut Vec<Vec<bool>>, res: &mut Vec<Cell>) {
-        if let Coords::RectCoords { row, col } = crds {
-            if filled[row][col] {
-                return;
-            }
-            filled[row][col] = true;
-            match self.rows[row][col] {
-                CellState::Empty(x) => {
-                    res.push(Cell {
-                        crds,
-                        state: CellState::Empty(x),
-                    });
-                    if x == 0 {
-                        let min_r = if row == 0 { row } else { row - 1 };
-                        let max_r = if row == self.heigth - 1 { row } else { row + 1 };
-                        let min_c = if col == 0 { col } else { col - 1 };
-                        let max_c = if col == self.width - 1 { col } else { col + 1 };
-                        for r in min_r..=max_r {
-                            for c in min_c..=max_c {
-                                self.fill_recursive(
-                                    Coords::RectCoords { row: r, col: c },
-                                    filled,
-                                    res,
-                                );
+    // index of a cell of the board files
+    fn index(&self, row: usize, col: usize) -> usize {
+        let (heigth, _) = self.topology.size();
+        let crds = self.grid_type.coords(row / heigth, row % heigth, col);
+        self.topology.index(crds).expect("cell off the board")
+    }
+
+    fn count_empties(&mut self) {
+        for i in 0..self.cells.len() {
+            if self.cells[i].is_mine() {
+                continue;
+            }
+            let cnt: i8 = self
+                .topology
+                .neighbour_indices(i)
+                .into_iter()
+                .map(|j| self.cells[j].weight())
+                .sum();
+            self.cells[i] = CellState::Empty(cnt);
+        }
+    }
+
+    // a zero with mines and anti-mines evening out around it opens its safe neighbours only
+    // a stack rather than recursion, an opening of a big board is too deep for the thread's stack
+    fn fill(&mut self, i: usize, res: &mut Vec<Cell>) {
+        let mut stack = vec![i];
+        while let Some(i) = stack.pop() {
+            if self.revealed[i] {
+                continue;
+            }
+            if let CellState::Empty(x) = self.cells[i] {
+                self.revealed[i] = true;
+                self.flags[i] = 0;
+                self.opened_cnt += 1;
+                res.push(Cell {
+                    crds: self.topology.coords(i),
+                    state: CellState::Empty(x),
+                });
+                if x == 0 {
+                    stack.extend(self.topology.neighbour_indices(i));
+                }
+            }
+        }
+    }
+
+    fn read_layout<I>(lines: &mut I) -> io::Result<Self>
+    where
+        I: Iterator<Item = io::Result<String>>,
+    {
+        let line = next_line(lines)?;
+        GridServerField::read_board(&line, lines)
+    }
+
+    // the layout after its header line
+    fn read_board<I>(line: &str, lines: &mut I) -> io::Result<Self>
+    where
+        I: Iterator<Item = io::Result<String>>,
+    {
+        let mut size: Vec<&str> = line.split_whitespace().collect();
+        let neighbourhood = header_neighbourhood(&mut size).map_err(invalid_data)?;
+        let parse = |x: &str| {
+            x.parse::<usize>()
+                .map_err(|_| invalid_data(format!("bad board size: {}", line)))
+        };
+        let (kind, heigth, width, depth) = match size.as_slice() {
+            [kind, heigth, width] => (*kind, parse(heigth)?, parse(width)?, 1),
+            [kind, heigth, width, depth] => (*kind, parse(heigth)?, parse(width)?, parse(depth)?),
+            _ => return Err(invalid_data(format!("bad board size: {}", line))),
+        };
+        let grid_type = match GridType::from_kind(kind, heigth, width, depth) {
+            Some(grid_type) if heigth > 0 && width > 0 && depth > 0 => grid_type,
+            _ => return Err(invalid_data(format!("bad board size: {}", line))),
+        };
+        if neighbourhood != Neighbourhood::Standard && !grid_type.has_square_cells() {
+            return Err(invalid_data(format!("bad neighbourhood: {}", line)));
+        }
+        let topology = grid_type
+            .topology(&neighbourhood)
+            .ok_or_else(|| invalid_data("endless board has no layout"))?;
+
+        let mut mines = Vec::with_capacity(heigth * depth);
+        for row in read_grid(lines, heigth * depth, width)? {
+            mines.push(
+                row.iter()
+                    .map(|x| match x {
+                        '*' => Ok(CellState::Mine(1)),
+                        '-' => Ok(CellState::AntiMine),
+                        '.' => Ok(CellState::Empty(0)),
+                        _ => match x.to_digit(10) {
+                            Some(x @ 2..) if x <= MAX_CELL_MINES as u32 => {
+                                Ok(CellState::Mine(x as u8))
                             }
-                        }
+                            _ => Err(invalid_data(format!("bad layout cell: {}", x))),
+                        },
+                    })
+                    .collect::<io::Result<Vec<_>>>()?,
+            );
+        }
+        Ok(GridServerField::from_layout(
+            grid_type,
+            neighbourhood,
+            topology,
+            mines,
+        ))
+    }
+
+    // the state after its seed and layout header lines
+    fn read_state<I>(seed: Option<u64>, header: &str, lines: &mut I) -> io::Result<Self>
+    where
+        I: Iterator<Item = io::Result<String>>,
+    {
+        let mut f = GridServerField::read_board(header, lines)?;
+        f.seed = seed;
+        let (heigth, width) = f.topology.size();
+
+        if next_line(lines)? != "view" {
+            return Err(invalid_data("expected board view"));
+        }
+        for (row, cells) in read_grid(lines, heigth * f.grid_type.depth(), width)?
+            .iter()
+            .enumerate()
+        {
+            for (col, x) in cells.iter().enumerate() {
+                let i = f.index(row, col);
+                match (x, f.cells[i]) {
+                    ('#', _) => {}
+                    ('*', state) if state.is_mine() => f.revealed[i] = true,
+                    (x, CellState::Empty(cnt)) if *x == opened_char(cnt) => {
+                        f.revealed[i] = true;
+                        f.opened_cnt += 1;
                     }
+                    (x, _) => match FLAG_CHARS.iter().find(|(c, _)| c == x) {
+                        Some(&(_, flag)) => f.flags[i] = flag,
+                        None => return Err(invalid_data(format!("bad view cell: {}", x))),
+                    },
                 }
-                _ => {}
             }
-        } else {
-            panic!("wrong coords type");
         }
+        Ok(f)
+    }
+
+    // one line per row of the board files
+    fn write_grid<F>(&self, out: &mut dyn Write, cell_char: F) -> io::Result<()>
+    where
+        F: Fn(usize) -> char,
+    {
+        let (heigth, width) = self.topology.size();
+        for row in 0..heigth * self.grid_type.depth() {
+            let line: String = (0..width)
+                .map(|col| cell_char(self.index(row, col)))
+                .collect();
+            writeln!(out, "{}", line)?;
+        }
+        Ok(())
     }
 }
 
-impl TServerField for RectServerField {
-    fn open_cells(&self, all_coords: Vec<Coords>) -> Vec<Cell> {
+// the flags of the save view, see is_flag_weight
+const FLAG_CHARS: [(char, i8); 4] = [('F', 1), ('D', 2), ('T', 3), ('A', -1)];
+
+// an opened cell of the save view
+fn opened_char(cnt: i8) -> char {
+    match cnt {
+        0..=35 => char::from_digit(cnt as u32, 36).unwrap(),
+        _ if cnt < 0 => '-',
+        _ => '+',
+    }
+}
+
+// one of the mines_cnt mines left to put in free_cnt cells, mostly a single mine,
+// an anti-mine takes a cell and needs another one for the mine it evens out
+fn random_weighted_mine(rng: &mut ChaCha8Rng, mines_cnt: usize, free_cnt: usize) -> CellState {
+    match rng.gen_range(0..8) {
+        0 if free_cnt > mines_cnt + 2 => CellState::AntiMine,
+        1 | 2 => CellState::Mine(mines_cnt.min(2) as u8),
+        3 => CellState::Mine(mines_cnt.min(MAX_CELL_MINES as usize) as u8),
+        _ => CellState::Mine(1),
+    }
+}
+
+fn read_grid<I>(lines: &mut I, heigth: usize, width: usize) -> io::Result<Vec<Vec<char>>>
+where
+    I: Iterator<Item = io::Result<String>>,
+{
+    let mut grid = Vec::with_capacity(heigth);
+    for _ in 0..heigth {
+        let line: Vec<char> = next_line(lines)?.trim_end().chars().collect();
+        if line.len() != width {
+            return Err(invalid_data(format!(
+                "expected {} cells in a row, got {}",
+                width,
+                line.len()
+            )));
+        }
+        grid.push(line);
+    }
+    Ok(grid)
+}
+
+// coords off the board are ignored
+impl TServerField for GridServerField {
+    fn open_cells(&mut self, all_coords: Vec<Coords>) -> Vec<Cell> {
         let mut res = Vec::<Cell>::new();
-        let mut filled = vec![vec![false; self.width]; self.heigth];
         for crds in all_coords {
-            if let Coords::RectCoords { row, col } = crds {
-                match self.rows[row][col] {
-                    CellState::Mine => res.push(Cell {
-                        crds,
-                        state: CellState::Mine,
-                    }),
-                    CellState::Empty(x) => {
-                        if x > 0 {
-                            res.push(Cell {
-                                crds,
-                                state: CellState::Empty(x),
-                            });
-                        } else {
-                            self.fill_recursive(crds, &mut filled, &mut res);
-                        }
+            let i = match self.topology.index(crds) {
+                Some(i) => i,
+                None => continue,
+            };
+            match self.cells[i] {
+                CellState::Empty(_) => self.fill(i, &mut res),
+                // a mine blown with lives to spare stays so
+                _ if self.revealed[i] => {}
+                state => {
+                    self.revealed[i] = true;
+                    res.push(Cell { crds, state })
+                }
+            }
+        }
+        res
+    }
+
+    fn close_cells(&mut self, coords: &[Coords]) {
+        for i in coords.iter().filter_map(|crds| self.topology.index(*crds)) {
+            if self.revealed[i] && !self.cells[i].is_mine() {
+                self.opened_cnt -= 1;
+            }
+            self.revealed[i] = false;
+        }
+    }
+
+    fn set_flag(&mut self, crds: Coords, flag: i8) {
+        if let Some(i) = self.topology.index(crds) {
+            if !self.revealed[i] && (flag == 0 || is_flag_weight(flag)) {
+                self.flags[i] = flag;
+            }
+        }
+    }
+
+    fn flag(&self, crds: Coords) -> i8 {
+        self.topology.index(crds).map_or(0, |i| self.flags[i])
+    }
+
+    // mines_cnt is weighted, the cells holding them are counted here
+    fn is_cleared(&self) -> bool {
+        let mine_cells = self.cells.iter().filter(|x| x.is_mine()).count();
+        self.opened_cnt == self.cells.len() - mine_cells
+    }
+
+    fn mines(&self) -> Vec<Cell> {
+        (0..self.cells.len())
+            .filter(|&i| self.cells[i].is_mine())
+            .map(|i| Cell {
+                crds: self.topology.coords(i),
+                state: self.cells[i],
+            })
+            .collect()
+    }
+
+    fn board_stats(&self) -> BoardStats {
+        // cells opened for free by clicking an opening: the zeros and their borders
+        let mut in_opening = vec![false; self.cells.len()];
+        let mut openings = 0;
+        let mut zeros = 0;
+        for (i, cell) in self.cells.iter().enumerate() {
+            if *cell != CellState::Empty(0) {
+                continue;
+            }
+            zeros += 1;
+            if in_opening[i] {
+                continue;
+            }
+            openings += 1;
+            in_opening[i] = true;
+            let mut stack = vec![i];
+            while let Some(i) = stack.pop() {
+                for j in self.topology.neighbour_indices(i) {
+                    if in_opening[j] || self.cells[j].is_mine() {
+                        continue;
+                    }
+                    in_opening[j] = true;
+                    if self.cells[j] == CellState::Empty(0) {
+                        stack.push(j);
+                    }
+                }
+            }
+        }
+
+        // every other safe cell needs its own click
+        let mut visited = in_opening;
+        let mut isolated = 0;
+        let mut islands = 0;
+        for i in 0..self.cells.len() {
+            if visited[i] || self.cells[i].is_mine() {
+                continue;
+            }
+            islands += 1;
+            visited[i] = true;
+            let mut stack = vec![i];
+            while let Some(i) = stack.pop() {
+                isolated += 1;
+                for j in self.topology.neighbour_indices(i) {
+                    if !visited[j] && !self.cells[j].is_mine() {
+                        visited[j] = true;
+                        stack.push(j);
                     }
                 }
+            }
+        }
+
+        BoardStats {
+            bbbv: openings + isolated,
+            openings,
+            islands,
+            zero_cells_percent: 100. * zeros as f32 / self.cells.len() as f32,
+        }
+    }
+
+    fn revealed_cells(&self) -> Vec<Cell> {
+        (0..self.cells.len())
+            .filter(|&i| self.revealed[i])
+            .map(|i| Cell {
+                crds: self.topology.coords(i),
+                state: self.cells[i],
+            })
+            .collect()
+    }
+
+    fn flags(&self) -> Vec<(Coords, i8)> {
+        (0..self.cells.len())
+            .filter(|&i| self.flags[i] != 0)
+            .map(|i| (self.topology.coords(i), self.flags[i]))
+            .collect()
+    }
+
+    fn init_params(&self) -> InitParams {
+        InitParams {
+            grid_type: self.grid_type.clone(),
+            mines_cnt: self.mines_cnt,
+            seed: self.seed,
+            practice: false,
+            neighbourhood: self.neighbourhood.clone(),
+            multi_mines: self.multi_mines,
+            // kept by the server
+            lives: 1,
+            mode: GameMode::Classic,
+        }
+    }
+
+    fn write_layout(&self, out: &mut dyn Write) -> io::Result<()> {
+        let (heigth, width) = self.topology.size();
+        write!(out, "{} {} {}", self.grid_type.kind(), heigth, width)?;
+        if let GridType::CubeGrid { depth, .. } = self.grid_type {
+            write!(out, " {}", depth)?;
+        }
+        if self.neighbourhood != Neighbourhood::Standard {
+            write!(out, " {}", self.neighbourhood)?;
+        }
+        writeln!(out)?;
+        self.write_grid(out, |i| match self.cells[i] {
+            CellState::Empty(_) => '.',
+            CellState::Mine(1) => '*',
+            CellState::Mine(x) => char::from_digit(x as u32, 10).unwrap(),
+            CellState::AntiMine => '-',
+        })
+    }
+
+    fn write_state(&self, out: &mut dyn Write) -> io::Result<()> {
+        match self.seed {
+            Some(seed) => writeln!(out, "seed {}", seed)?,
+            None => writeln!(out, "seed -")?,
+        }
+        self.write_layout(out)?;
+        writeln!(out, "view")?;
+        self.write_grid(out, |i| match self.cells[i] {
+            _ if self.flags[i] != 0 => FLAG_CHARS
+                .iter()
+                .find(|(_, flag)| *flag == self.flags[i])
+                .map_or('F', |(c, _)| *c),
+            _ if !self.revealed[i] => '#',
+            CellState::Empty(x) => opened_char(x),
+            _ => '*',
+        })
+    }
+
+    fn opened_cnt(&self) -> usize {
+        self.opened_cnt
+    }
+
+    // rect boards only, the row gets the mines of an average row, single ones,
+    // the row-major indices of the cells above stay the same
+    fn add_row(&mut self) -> bool {
+        let (heigth, width) = match self.grid_type {
+            GridType::RectGrid { heigth, width } => (heigth, width),
+            _ => return false,
+        };
+        let mines_cnt = usize::min(usize::max(self.mines_cnt / heigth, 1), width - 1);
+        let row_seed = self.seed.unwrap_or(0) ^ (heigth as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        let mut rng = ChaCha8Rng::seed_from_u64(row_seed);
+        self.cells.extend(vec![CellState::Empty(0); width]);
+        self.revealed.extend(vec![false; width]);
+        self.flags.extend(vec![0; width]);
+        let mut placed = 0;
+        while placed < mines_cnt {
+            let i = heigth * width + rng.gen_range(0..width as u32) as usize;
+            if !self.cells[i].is_mine() {
+                self.cells[i] = CellState::Mine(1);
+                placed += 1;
+            }
+        }
+        self.mines_cnt += mines_cnt;
+        self.grid_type = GridType::RectGrid {
+            heigth: heigth + 1,
+            width,
+        };
+        self.topology = Box::new(RectTopology {
+            heigth: heigth + 1,
+            width,
+            wrap: false,
+            offsets: self.neighbourhood.offsets(),
+        });
+        self.count_empties();
+        // the opened zeros left next to the new row open it like they would have
+        let zeros: Vec<usize> = (0..self.cells.len())
+            .filter(|&i| self.revealed[i] && self.cells[i] == CellState::Empty(0))
+            .collect();
+        let mut opened = Vec::new();
+        for i in zeros {
+            for j in self.topology.neighbour_indices(i) {
+                self.fill(j, &mut opened);
+            }
+        }
+        true
+    }
+}
+
+// how far one fill of an endless board reaches, in chunks from the opened cell,
+// some neighbourhoods open for good even at their floor of mines
+const FILL_CHUNKS: i64 = 2;
+
+// endless board, a chunk gets its mines once a cell next to it is opened or it's shown
+struct EndlessServerField {
+    // per chunk
+    mines_cnt: usize,
+    seed: u64,
+    neighbourhood: Neighbourhood,
+    topology: PlaneTopology,
+    // mines of the chunks generated so far, row by row
+    chunks: HashMap<(i64, i64), Vec<bool>>,
+    revealed: HashMap<Coords, CellState>,
+    flagged: HashSet<Coords>,
+    opened_cnt: usize,
+    // chunks in the window of the client
+    shown: HashSet<(i64, i64)>,
+}
+
+impl EndlessServerField {
+    fn new(neighbourhood: Neighbourhood, mines_cnt: usize, seed: u64) -> Self {
+        if !(neighbourhood.min_chunk_mines()..=MAX_CHUNK_MINES).contains(&mines_cnt) {
+            panic!("invalid field params!")
+        }
+        EndlessServerField {
+            mines_cnt,
+            seed,
+            topology: PlaneTopology {
+                offsets: neighbourhood.offsets(),
+            },
+            neighbourhood,
+            chunks: HashMap::new(),
+            revealed: HashMap::new(),
+            flagged: HashSet::new(),
+            opened_cnt: 0,
+            shown: HashSet::new(),
+        }
+    }
+
+    fn chunk_mines(&mut self, chunk: (i64, i64)) -> &Vec<bool> {
+        let (mines_cnt, seed) = (self.mines_cnt, self.seed);
+        self.chunks
+            .entry(chunk)
+            .or_insert_with(|| generate_chunk(chunk, mines_cnt, seed))
+    }
+
+    // coords other than PlaneCoords are never mines
+    fn is_mine(&mut self, crds: Coords) -> bool {
+        match crds {
+            Coords::PlaneCoords { row, col } => {
+                self.chunk_mines(chunk_of(row, col))[chunk_index(row, col)]
+            }
+            _ => false,
+        }
+    }
+
+    fn cell_state(&mut self, crds: Coords) -> CellState {
+        if self.is_mine(crds) {
+            return CellState::Mine(1);
+        }
+        let cnt = self
+            .topology
+            .neighbours(crds)
+            .into_iter()
+            .filter(|&crds| self.is_mine(crds))
+            .count();
+        CellState::Empty(cnt as i8)
+    }
+
+    // a zero opens its neighbours, whichever chunk they are in, up to FILL_CHUNKS chunks away,
+    // the zeros at the edge are left for the next click
+    fn fill(&mut self, crds: Coords, res: &mut Vec<Cell>) {
+        let near = |crds: Coords, start: (i64, i64)| match crds {
+            Coords::PlaneCoords { row, col } => {
+                let (chunk_row, chunk_col) = chunk_of(row, col);
+                (chunk_row - start.0).abs() <= FILL_CHUNKS
+                    && (chunk_col - start.1).abs() <= FILL_CHUNKS
+            }
+            _ => false,
+        };
+        let start = match crds {
+            Coords::PlaneCoords { row, col } => chunk_of(row, col),
+            _ => return,
+        };
+        let mut stack = vec![crds];
+        while let Some(crds) = stack.pop() {
+            if self.revealed.contains_key(&crds) || !near(crds, start) {
+                continue;
+            }
+            let state = self.cell_state(crds);
+            self.revealed.insert(crds, state);
+            self.flagged.remove(&crds);
+            self.opened_cnt += 1;
+            res.push(Cell { crds, state });
+            if state == CellState::Empty(0) {
+                stack.extend(self.topology.neighbours(crds));
+            }
+        }
+    }
+
+    fn read_state<I>(seed: Option<u64>, header: &str, lines: &mut I) -> io::Result<Self>
+    where
+        I: Iterator<Item = io::Result<String>>,
+    {
+        let mut words: Vec<&str> = header.split_whitespace().collect();
+        let neighbourhood = header_neighbourhood(&mut words).map_err(invalid_data)?;
+        let mines_cnt = match words.as_slice() {
+            ["endless", mines_cnt] => mines_cnt
+                .parse()
+                .map_err(|_| invalid_data(format!("bad mines count: {}", header)))?,
+            _ => return Err(invalid_data(format!("bad board size: {}", header))),
+        };
+        if !(neighbourhood.min_chunk_mines()..=MAX_CHUNK_MINES).contains(&mines_cnt) {
+            return Err(invalid_data(format!("bad mines count: {}", header)));
+        }
+        let seed = seed.ok_or_else(|| invalid_data("endless board needs a seed"))?;
+        let mut f = EndlessServerField::new(neighbourhood, mines_cnt, seed);
+
+        let mut read_coords = |name: &str| {
+            let line = next_line(lines)?;
+            let mut words = line.split_whitespace();
+            if words.next() != Some(name) {
+                return Err(invalid_data(format!("expected {}: {}", name, line)));
+            }
+            words
+                .map(|x| match GridType::EndlessGrid.parse_coords(x) {
+                    Ok(Coords::PlaneCoords { row, col }) => Ok((row, col)),
+                    _ => Err(invalid_data(format!("bad coords: {}", x))),
+                })
+                .collect::<io::Result<Vec<_>>>()
+        };
+        let opened = read_coords("opened")?;
+        let flags = read_coords("flags")?;
+        for (row, col) in opened {
+            let crds = Coords::PlaneCoords { row, col };
+            let state = f.cell_state(crds);
+            if !state.is_mine() {
+                f.opened_cnt += 1;
+            }
+            f.revealed.insert(crds, state);
+        }
+        for (row, col) in flags {
+            f.flagged.insert(Coords::PlaneCoords { row, col });
+        }
+        Ok(f)
+    }
+}
+
+// depends on the seed and the chunk coords only, so the chunks can be generated in any order,
+// the cells around 0,0 are kept safe for the first click
+fn generate_chunk(chunk: (i64, i64), mut mines_cnt: usize, seed: u64) -> Vec<bool> {
+    let (chunk_row, chunk_col) = chunk;
+    let chunk_seed = seed
+        ^ (chunk_row as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+        ^ (chunk_col as u64).wrapping_mul(0xc2b2_ae3d_27d4_eb4f);
+    let mut rng = ChaCha8Rng::seed_from_u64(chunk_seed);
+    let mut mines = vec![false; CHUNK_CELLS];
+    while mines_cnt > 0 {
+        let (r, c) = (rng.gen_range(0..CHUNK_SIZE), rng.gen_range(0..CHUNK_SIZE));
+        let (row, col) = (chunk_row * CHUNK_SIZE + r, chunk_col * CHUNK_SIZE + c);
+        let i = chunk_index(row, col);
+        if !mines[i] && (row.abs() > 1 || col.abs() > 1) {
+            mines[i] = true;
+            mines_cnt -= 1;
+        }
+    }
+    mines
+}
+
+// coords other than PlaneCoords are ignored
+impl TServerField for EndlessServerField {
+    fn open_cells(&mut self, all_coords: Vec<Coords>) -> Vec<Cell> {
+        let mut res = Vec::<Cell>::new();
+        for crds in all_coords {
+            if !matches!(crds, Coords::PlaneCoords { .. }) || self.revealed.contains_key(&crds) {
+                continue;
+            }
+            if self.is_mine(crds) {
+                self.revealed.insert(crds, CellState::Mine(1));
+                res.push(Cell {
+                    crds,
+                    state: CellState::Mine(1),
+                })
             } else {
-                panic!("wrong coords type");
+                self.fill(crds, &mut res);
+            }
+        }
+        res
+    }
+
+    fn close_cells(&mut self, coords: &[Coords]) {
+        for crds in coords {
+            if let Some(state) = self.revealed.remove(crds) {
+                if !state.is_mine() {
+                    self.opened_cnt -= 1;
+                }
+            }
+        }
+    }
+
+    // cells hold one mine at most, any flag is one of one mine
+    fn set_flag(&mut self, crds: Coords, flag: i8) {
+        if !matches!(crds, Coords::PlaneCoords { .. }) || self.revealed.contains_key(&crds) {
+            return;
+        }
+        if flag != 0 {
+            self.flagged.insert(crds);
+        } else {
+            self.flagged.remove(&crds);
+        }
+    }
+
+    fn flag(&self, crds: Coords) -> i8 {
+        self.flagged.contains(&crds) as i8
+    }
+
+    fn is_cleared(&self) -> bool {
+        false
+    }
+
+    // of the shown chunks, they are generated when shown
+    fn mines(&self) -> Vec<Cell> {
+        let mut res = Vec::new();
+        for chunk in &self.shown {
+            let mines = match self.chunks.get(chunk) {
+                Some(mines) => mines,
+                None => continue,
+            };
+            for (i, _) in mines.iter().enumerate().filter(|(_, mine)| **mine) {
+                let (row, col) = chunk_cell(*chunk, i);
+                res.push(Cell {
+                    crds: Coords::PlaneCoords { row, col },
+                    state: CellState::Mine(1),
+                });
             }
         }
         res
     }
+
+    // an endless board can't be cleared, there are no clicks to count
+    fn board_stats(&self) -> BoardStats {
+        BoardStats {
+            bbbv: 0,
+            openings: 0,
+            islands: 0,
+            zero_cells_percent: 0.,
+        }
+    }
+
+    fn revealed_cells(&self) -> Vec<Cell> {
+        self.revealed
+            .iter()
+            .filter(|(crds, _)| self.is_shown(**crds))
+            .map(|(crds, state)| Cell {
+                crds: *crds,
+                state: *state,
+            })
+            .collect()
+    }
+
+    fn flags(&self) -> Vec<(Coords, i8)> {
+        self.flagged
+            .iter()
+            .filter(|crds| self.is_shown(**crds))
+            .map(|crds| (*crds, 1))
+            .collect()
+    }
+
+    fn init_params(&self) -> InitParams {
+        InitParams {
+            grid_type: GridType::EndlessGrid,
+            mines_cnt: self.mines_cnt,
+            seed: Some(self.seed),
+            practice: false,
+            neighbourhood: self.neighbourhood.clone(),
+            multi_mines: false,
+            // kept by the server
+            lives: 1,
+            mode: GameMode::Classic,
+        }
+    }
+
+    fn write_layout(&self, _out: &mut dyn Write) -> io::Result<()> {
+        Err(io::Error::other("endless board has no layout"))
+    }
+
+    fn write_state(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "seed {}", self.seed)?;
+        write!(out, "endless {}", self.mines_cnt)?;
+        if self.neighbourhood != Neighbourhood::Standard {
+            write!(out, " {}", self.neighbourhood)?;
+        }
+        writeln!(out)?;
+        write!(out, "opened")?;
+        for crds in self.revealed.keys() {
+            write!(out, " {}", crds)?;
+        }
+        writeln!(out)?;
+        write!(out, "flags")?;
+        for crds in &self.flagged {
+            write!(out, " {}", crds)?;
+        }
+        writeln!(out)
+    }
+
+    fn opened_cnt(&self) -> usize {
+        self.opened_cnt
+    }
+
+    fn show_chunks(&mut self, chunks: Vec<(i64, i64)>) -> (Vec<Cell>, Vec<(Coords, i8)>) {
+        let old = std::mem::replace(&mut self.shown, chunks.into_iter().collect());
+        for chunk in self.shown.clone() {
+            self.chunk_mines(chunk);
+        }
+        let is_new = |crds: &Coords| match *crds {
+            Coords::PlaneCoords { row, col } => !old.contains(&chunk_of(row, col)),
+            _ => false,
+        };
+        let cells = self
+            .revealed_cells()
+            .into_iter()
+            .filter(|cell| is_new(&cell.crds))
+            .collect();
+        let flags = self
+            .flags()
+            .into_iter()
+            .filter(|(crds, _)| is_new(crds))
+            .collect();
+        (cells, flags)
+    }
+
+    fn is_shown(&self, crds: Coords) -> bool {
+        match crds {
+            Coords::PlaneCoords { row, col } => self.shown.contains(&chunk_of(row, col)),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("rs-minesweeper-{}-{}", std::process::id(), name))
+    }
+
+    fn params(grid_type: GridType, mines_cnt: usize, multi_mines: bool) -> InitParams {
+        InitParams {
+            grid_type,
+            mines_cnt,
+            seed: Some(1),
+            practice: false,
+            neighbourhood: Neighbourhood::Standard,
+            multi_mines,
+            lives: 1,
+            mode: GameMode::Classic,
+        }
+    }
+
+    fn round_trip_params() -> Vec<InitParams> {
+        vec![
+            InitParams {
+                practice: true,
+                lives: 3,
+                ..params(
+                    GridType::RectGrid {
+                        heigth: 9,
+                        width: 9,
+                    },
+                    10,
+                    false,
+                )
+            },
+            params(
+                GridType::RectGrid {
+                    heigth: 9,
+                    width: 9,
+                },
+                10,
+                false,
+            ),
+            params(
+                GridType::TorusGrid {
+                    heigth: 9,
+                    width: 9,
+                },
+                10,
+                false,
+            ),
+            params(
+                GridType::TriGrid {
+                    heigth: 8,
+                    width: 12,
+                },
+                15,
+                false,
+            ),
+            params(
+                GridType::CubeGrid {
+                    heigth: 4,
+                    width: 4,
+                    depth: 4,
+                },
+                10,
+                false,
+            ),
+            params(
+                GridType::RectGrid {
+                    heigth: 9,
+                    width: 9,
+                },
+                20,
+                true,
+            ),
+            InitParams {
+                neighbourhood: Neighbourhood::Knight,
+                ..params(
+                    GridType::RectGrid {
+                        heigth: 9,
+                        width: 9,
+                    },
+                    10,
+                    false,
+                )
+            },
+        ]
+    }
+
+    // a game with some cells opened, flags on mines of every weight and a wrong flag
+    fn played_game(params: &InitParams) -> Server {
+        let mut server = Server::new();
+        server.new_game(params);
+        let mines: Vec<Coords> = server.mines().iter().map(|cell| cell.crds).collect();
+        let safe: Vec<Coords> = params
+            .grid_type
+            .topology(&params.neighbourhood)
+            .unwrap()
+            .cells()
+            .into_iter()
+            .filter(|crds| !mines.contains(crds))
+            .collect();
+        let weights: &[i8] = if params.multi_mines {
+            &[1, 2, 3, -1]
+        } else {
+            &[1]
+        };
+        for (&crds, &flag) in mines.iter().zip(weights) {
+            server.process_client_data(ClientMessage::SetFlag { crds, flag });
+        }
+        server.process_client_data(ClientMessage::SetFlag {
+            crds: safe[safe.len() - 1],
+            flag: 1,
+        });
+        server.process_client_data(ClientMessage::OpenCells(vec![
+            safe[0],
+            safe[safe.len() / 2],
+        ]));
+        server
+    }
+
+    // the mines, the opened cells and the flags in text form
+    fn snapshot(server: &Server) -> Vec<String> {
+        let field = match &server.game_state {
+            GameState::InGame { field } | GameState::GameOver { field, .. } => field,
+            GameState::NotInGame => return vec![],
+        };
+        let mut res: Vec<String> = field
+            .mines()
+            .iter()
+            .map(|cell| format!("mine {}:{}", cell.crds, cell.state))
+            .chain(
+                field
+                    .revealed_cells()
+                    .iter()
+                    .map(|cell| format!("open {}:{}", cell.crds, cell.state)),
+            )
+            .chain(
+                field
+                    .flags()
+                    .iter()
+                    .map(|(crds, flag)| format!("flag {}:{}", crds, flag)),
+            )
+            .collect();
+        res.sort();
+        res
+    }
+
+    // saved, loaded and saved again to the same file
+    fn assert_save_round_trip(server: &Server, name: &str) -> String {
+        let (first, second) = (temp_path(name), temp_path(&format!("{}-again", name)));
+        server.save_game(&first).unwrap();
+        let mut loaded = Server::new();
+        loaded.load_game(&first).unwrap();
+        loaded.save_game(&second).unwrap();
+        let text = fs::read_to_string(&first).unwrap();
+        assert_eq!(text, fs::read_to_string(&second).unwrap());
+        assert_eq!(snapshot(server), snapshot(&loaded));
+        assert_eq!(server.is_in_game(), loaded.is_in_game());
+        assert_eq!(server.history.is_some(), loaded.history.is_some());
+        fs::remove_file(first).unwrap();
+        fs::remove_file(second).unwrap();
+        text
+    }
+
+    #[test]
+    fn save_round_trip() {
+        for (i, params) in round_trip_params().iter().enumerate() {
+            assert_save_round_trip(&played_game(params), &format!("save-{}", i));
+        }
+    }
+
+    #[test]
+    fn save_round_trip_out_of_range_counts() {
+        for (mine, count) in [(CellState::Mine(3), "#+#"), (CellState::AntiMine, "#-#")] {
+            // the center of a cube of mines, 26 neighbours of 3 mines or of an anti-mine
+            let mut mines = vec![vec![mine; 3]; 9];
+            mines[4][1] = CellState::Empty(0);
+            let grid_type = GridType::CubeGrid {
+                heigth: 3,
+                width: 3,
+                depth: 3,
+            };
+            let topology = grid_type.topology(&Neighbourhood::Standard).unwrap();
+            let field =
+                GridServerField::from_layout(grid_type, Neighbourhood::Standard, topology, mines);
+            let mut server = Server::new();
+            server.game_state = GameState::InGame {
+                field: Box::new(field),
+            };
+            server.process_client_data(ClientMessage::OpenCells(vec![Coords::CubeCoords {
+                layer: 1,
+                row: 1,
+                col: 1,
+            }]));
+            let text = assert_save_round_trip(&server, &format!("save-count-{}", count));
+            assert!(text.lines().any(|line| line == count));
+        }
+    }
+
+    fn layout_field(layout: &str) -> GridServerField {
+        let mut lines = layout.lines().map(|line| Ok(line.to_string()));
+        GridServerField::read_layout(&mut lines).unwrap()
+    }
+
+    #[test]
+    fn board_stats() {
+        // one opening reaching every safe cell
+        let stats = layout_field("rect 3 5\n*....\n.....\n....*").board_stats();
+        assert_eq!((stats.bbbv, stats.openings, stats.islands), (1, 1, 0));
+        assert_eq!(stats.zero_cells_percent, 100. * 7. / 15.);
+        // an opening on the left and an island of two cells in the right column
+        let stats = layout_field("rect 3 5\n...*.\n...*.\n...**").board_stats();
+        assert_eq!((stats.bbbv, stats.openings, stats.islands), (3, 1, 1));
+        assert_eq!(stats.zero_cells_percent, 40.);
+        // no zeros, every safe cell is an island of its own
+        let stats = layout_field("rect 1 5\n.*.*.").board_stats();
+        assert_eq!((stats.bbbv, stats.openings, stats.islands), (3, 0, 3));
+        assert_eq!(stats.zero_cells_percent, 0.);
+    }
+
+    #[test]
+    fn grown_rows_open_next_to_zeros() {
+        let mut field = layout_field("rect 3 5\n*....\n.....\n.....");
+        field.open_cells(vec![Coords::RectCoords { row: 2, col: 4 }]);
+        assert!(field.add_row());
+        for i in 0..field.cells.len() {
+            if field.revealed[i] && field.cells[i] == CellState::Empty(0) {
+                let neighbours = field.topology.neighbour_indices(i);
+                assert!(neighbours.into_iter().all(|j| field.revealed[j]));
+            }
+        }
+        assert!((15..20).any(|i| field.revealed[i]));
+    }
+
+    #[test]
+    fn fill_big_boards() {
+        // deeper than the stack of a test thread if filled recursively
+        let cube = GridType::CubeGrid {
+            heigth: 40,
+            width: 40,
+            depth: 40,
+        };
+        for (grid_type, cells_cnt) in [
+            (
+                GridType::RectGrid {
+                    heigth: 200,
+                    width: 200,
+                },
+                200 * 200,
+            ),
+            (cube, 40 * 40 * 40),
+        ] {
+            let topology = grid_type.topology(&Neighbourhood::Standard).unwrap();
+            let width = topology.size().1;
+            let mut mines = vec![vec![CellState::Empty(0); width]; cells_cnt / width];
+            mines[0][0] = CellState::Mine(1);
+            let mut field =
+                GridServerField::from_layout(grid_type, Neighbourhood::Standard, topology, mines);
+            let far = field.topology.coords(cells_cnt - 1);
+            assert_eq!(field.open_cells(vec![far]).len(), cells_cnt - 1);
+            assert!(field.is_cleared());
+        }
+    }
+
+    #[test]
+    fn fill_endless_boards() {
+        let cells_cnt = ((2 * FILL_CHUNKS + 1) * (2 * FILL_CHUNKS + 1)) as usize * CHUNK_CELLS;
+        for neighbourhood in [Neighbourhood::Knight, Neighbourhood::Cross] {
+            let mines_cnt = neighbourhood.min_chunk_mines();
+            let mut field = EndlessServerField::new(neighbourhood, mines_cnt, 6);
+            let opened = field.open_cells(vec![Coords::PlaneCoords { row: 0, col: 0 }]);
+            assert!(!opened.is_empty() && opened.len() <= cells_cnt);
+        }
+    }
+
+    #[test]
+    fn layout_round_trip() {
+        for (i, params) in round_trip_params().iter().enumerate() {
+            let mut server = Server::new();
+            server.new_game(params);
+            let (first, second) = (
+                temp_path(&format!("layout-{}", i)),
+                temp_path(&format!("layout-{}-again", i)),
+            );
+            server.export_layout(&first).unwrap();
+            let mut loaded = Server::new();
+            loaded.load_layout(&first).unwrap();
+            loaded.export_layout(&second).unwrap();
+            assert_eq!(
+                fs::read_to_string(&first).unwrap(),
+                fs::read_to_string(&second).unwrap()
+            );
+            assert_eq!(snapshot(&server), snapshot(&loaded));
+            fs::remove_file(first).unwrap();
+            fs::remove_file(second).unwrap();
+        }
+    }
 }
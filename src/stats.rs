@@ -0,0 +1,159 @@
+use crate::common::*;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+// game records file, one finished game per line:
+//   <unix time> <preset> <seed|-> <time ms> <won|lost> <3bv> <clicks> <hints> [practice]
+// practice games are kept but left out of every statistic
+pub struct GameRecord {
+    pub finished_at: u64,
+    pub preset: String,
+    pub seed: Option<u64>,
+    pub time: Duration,
+    pub won: bool,
+    pub bbbv: usize,
+    pub clicks: usize,
+    pub hints: usize,
+    pub practice: bool,
+}
+
+impl fmt::Display for GameRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} ", self.finished_at, self.preset)?;
+        match self.seed {
+            Some(seed) => write!(f, "{} ", seed)?,
+            None => write!(f, "- ")?,
+        }
+        write!(
+            f,
+            "{} {} {} {} {}",
+            self.time.as_millis(),
+            if self.won { "won" } else { "lost" },
+            self.bbbv,
+            self.clicks,
+            self.hints
+        )?;
+        if self.practice {
+            write!(f, " practice")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for GameRecord {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad_record = || format!("bad game record: {}", s);
+        let mut words: Vec<&str> = s.split_whitespace().collect();
+        let practice = words.last() == Some(&"practice");
+        if practice {
+            words.pop();
+        }
+        let [finished_at, preset, seed, time, result, bbbv, clicks, hints] = words.as_slice()
+        else {
+            return Err(bad_record());
+        };
+        Ok(GameRecord {
+            finished_at: finished_at.parse().map_err(|_| bad_record())?,
+            preset: preset.to_string(),
+            seed: match *seed {
+                "-" => None,
+                seed => Some(seed.parse().map_err(|_| bad_record())?),
+            },
+            time: Duration::from_millis(time.parse().map_err(|_| bad_record())?),
+            won: match *result {
+                "won" => true,
+                "lost" => false,
+                _ => return Err(bad_record()),
+            },
+            bbbv: bbbv.parse().map_err(|_| bad_record())?,
+            clicks: clicks.parse().map_err(|_| bad_record())?,
+            hints: hints.parse().map_err(|_| bad_record())?,
+            practice,
+        })
+    }
+}
+
+#[derive(Default)]
+pub struct PlayerStats {
+    // in the order the games were played
+    games: Vec<GameRecord>,
+}
+
+impl PlayerStats {
+    // a missing file means no games were played yet
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<PlayerStats> {
+        Ok(PlayerStats {
+            games: load_lines(path)?,
+        })
+    }
+
+    pub fn append<P: AsRef<Path>>(path: P, record: &GameRecord) -> io::Result<()> {
+        append_line(path, record)
+    }
+
+    fn ranked_games(&self) -> impl Iterator<Item = &GameRecord> {
+        self.games.iter().filter(|game| !game.practice)
+    }
+
+    pub fn games_cnt(&self) -> usize {
+        self.ranked_games().count()
+    }
+
+    pub fn wins_cnt(&self) -> usize {
+        self.ranked_games().filter(|game| game.won).count()
+    }
+
+    // (current, longest) win streaks
+    pub fn streaks(&self) -> (usize, usize) {
+        let (mut current, mut longest) = (0, 0);
+        for game in self.ranked_games() {
+            current = if game.won { current + 1 } else { 0 };
+            longest = usize::max(longest, current);
+        }
+        (current, longest)
+    }
+
+    pub fn best_times(&self) -> BTreeMap<&str, Duration> {
+        let mut res = BTreeMap::new();
+        for game in self.ranked_games().filter(|game| game.won) {
+            res.entry(game.preset.as_str())
+                .and_modify(|best: &mut Duration| *best = Duration::min(*best, game.time))
+                .or_insert(game.time);
+        }
+        res
+    }
+
+    // won game times of a preset split into equal buckets between the best and the worst one
+    pub fn time_histogram(&self, preset: &str, buckets_cnt: usize) -> Option<TimeHistogram> {
+        let times: Vec<Duration> = self
+            .ranked_games()
+            .filter(|game| game.won && game.preset == preset)
+            .map(|game| game.time)
+            .collect();
+        let min = *times.iter().min()?;
+        let max = *times.iter().max()?;
+        let bucket_size = (max - min).as_secs_f32() / buckets_cnt as f32;
+        let mut buckets = vec![0; buckets_cnt];
+        for time in times {
+            let i = if bucket_size > 0. {
+                ((time - min).as_secs_f32() / bucket_size) as usize
+            } else {
+                0
+            };
+            buckets[usize::min(i, buckets_cnt - 1)] += 1;
+        }
+        Some(TimeHistogram { min, max, buckets })
+    }
+}
+
+pub struct TimeHistogram {
+    pub min: Duration,
+    pub max: Duration,
+    pub buckets: Vec<usize>,
+}
@@ -0,0 +1,342 @@
+use crate::common::*;
+use macroquad::prelude::*;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+pub const THEMES_DIR: &str = "assets/themes";
+
+// theme file, one "key = value" per line, '#' starts a comment line:
+//   background, grid_line, closed, highlighted, opened, marked, mine, blown_mine, cursor,
+//   question (the question mark drawn over a closed cell),
+//   number_1 .. number_8 = #rrggbb or #rrggbbaa
+//   sprites = <png path relative to the theme file>, tiles in a row:
+//             closed, flag, mine, blown mine
+//   sprite_size = <tile size in pixels>
+//   font = <ttf path relative to the theme file>
+#[derive(Clone)]
+pub struct Theme {
+    pub background: Color,
+    pub grid_line: Color,
+    pub closed: Color,
+    pub highlighted: Color,
+    pub opened: Color,
+    pub marked: Color,
+    pub mine: Color,
+    pub blown_mine: Color,
+    pub cursor: Color,
+    pub question: Color,
+    pub numbers: [Color; 8],
+    pub sprites: Option<SpriteSheet>,
+    pub font: Option<Font>,
+    // set by the accessibility options, not by theme files
+    pub glyphs: bool,
+    pub glyph_color: Color,
+    pub text_scale: f32,
+}
+
+pub enum Sprite {
+    Closed,
+    Flag,
+    Mine,
+    BlownMine,
+}
+
+#[derive(Clone)]
+pub struct SpriteSheet {
+    texture: Texture2D,
+    tile_size: f32,
+}
+
+impl SpriteSheet {
+    pub fn draw(&self, sprite: Sprite, x: f32, y: f32, size: f32) {
+        let i = match sprite {
+            Sprite::Closed => 0,
+            Sprite::Flag => 1,
+            Sprite::Mine => 2,
+            Sprite::BlownMine => 3,
+        };
+        draw_texture_ex(
+            &self.texture,
+            x,
+            y,
+            WHITE,
+            DrawTextureParams {
+                dest_size: Some(vec2(size, size)),
+                source: Some(Rect::new(
+                    i as f32 * self.tile_size,
+                    0.,
+                    self.tile_size,
+                    self.tile_size,
+                )),
+                ..Default::default()
+            },
+        );
+    }
+}
+
+// the colors the board was always drawn with
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            background: LIGHTGRAY,
+            grid_line: GRAY,
+            closed: DARKGRAY,
+            highlighted: WHITE,
+            opened: WHITE,
+            marked: YELLOW,
+            mine: BLACK,
+            blown_mine: RED,
+            cursor: BLUE,
+            question: WHITE,
+            numbers: [GOLD; 8],
+            sprites: None,
+            font: None,
+            glyphs: false,
+            glyph_color: BLACK,
+            text_scale: 1.,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Default)]
+pub enum Palette {
+    #[default]
+    Theme,
+    // Okabe-Ito colors, told apart with any kind of color blindness
+    ColorblindSafe,
+    HighContrast,
+}
+
+impl Palette {
+    pub fn next(self) -> Palette {
+        match self {
+            Palette::Theme => Palette::ColorblindSafe,
+            Palette::ColorblindSafe => Palette::HighContrast,
+            Palette::HighContrast => Palette::Theme,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Palette::Theme => "theme",
+            Palette::ColorblindSafe => "colorblind safe",
+            Palette::HighContrast => "high contrast",
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct Accessibility {
+    pub palette: Palette,
+    // flags and mines drawn as shapes rather than told apart by fill color
+    pub glyphs: bool,
+    pub large_text: bool,
+}
+
+const LARGE_TEXT_SCALE: f32 = 1.4;
+
+fn hex_colors<const N: usize>(colors: [u32; N]) -> [Color; N] {
+    colors.map(Color::from_hex)
+}
+
+pub enum Glyph {
+    Flag,
+    WrongFlag,
+    Mine,
+    BlownMine,
+}
+
+// shapes that stay recognizable without color
+pub fn draw_glyph(glyph: Glyph, x: f32, y: f32, size: f32, color: Color) {
+    let (cx, cy) = (x + size / 2., y + size / 2.);
+    let thickness = f32::max(1., size / 12.);
+    match glyph {
+        Glyph::Flag | Glyph::WrongFlag => {
+            let pole_x = x + size * 0.6;
+            draw_line(
+                pole_x,
+                y + size * 0.2,
+                pole_x,
+                y + size * 0.8,
+                thickness,
+                color,
+            );
+            draw_triangle(
+                vec2(pole_x, y + size * 0.2),
+                vec2(pole_x, y + size * 0.5),
+                vec2(x + size * 0.25, y + size * 0.35),
+                color,
+            );
+            draw_line(
+                x + size * 0.3,
+                y + size * 0.8,
+                x + size * 0.8,
+                y + size * 0.8,
+                thickness,
+                color,
+            );
+            if let Glyph::WrongFlag = glyph {
+                draw_cross(x, y, size, thickness, color);
+            }
+        }
+        Glyph::Mine | Glyph::BlownMine => {
+            let r = size * 0.22;
+            draw_circle(cx, cy, r, color);
+            for (dx, dy) in [(1., 0.), (0., 1.), (0.7, 0.7), (0.7, -0.7)] {
+                let len = size * 0.35;
+                draw_line(
+                    cx - dx * len,
+                    cy - dy * len,
+                    cx + dx * len,
+                    cy + dy * len,
+                    thickness,
+                    color,
+                );
+            }
+            if let Glyph::BlownMine = glyph {
+                // a ring around the mine that was opened
+                draw_circle_lines(cx, cy, size * 0.45, thickness, color);
+            }
+        }
+    }
+}
+
+// a flag put on a safe cell is crossed out
+pub fn draw_cross(x: f32, y: f32, size: f32, thickness: f32, color: Color) {
+    let (near, far) = (size * 0.15, size * 0.85);
+    draw_line(x + near, y + near, x + far, y + far, thickness, color);
+    draw_line(x + far, y + near, x + near, y + far, thickness, color);
+}
+
+fn parse_color(s: &str) -> Result<Color, String> {
+    let bad_color = || format!("bad color: {}", s);
+    let hex = s.strip_prefix('#').ok_or_else(bad_color)?;
+    if !(hex.len() == 6 || hex.len() == 8) || !hex.is_ascii() {
+        return Err(bad_color());
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| bad_color());
+    let alpha = if hex.len() == 8 { channel(6)? } else { 255 };
+    Ok(Color::from_rgba(
+        channel(0)?,
+        channel(2)?,
+        channel(4)?,
+        alpha,
+    ))
+}
+
+impl Theme {
+    // a theme name from THEMES_DIR or a path to a theme file
+    pub fn load(name: &str) -> io::Result<Theme> {
+        let path = if name.contains('/') || name.ends_with(".theme") {
+            Path::new(name).to_path_buf()
+        } else {
+            Path::new(THEMES_DIR).join(format!("{}.theme", name))
+        };
+        let dir = path.parent().unwrap_or(Path::new("."));
+
+        let mut theme = Theme::default();
+        let mut sprites = None;
+        let mut sprite_size = None;
+        for line in fs::read_to_string(&path)?.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| invalid_data(format!("bad theme line: {}", line)))?;
+            let (key, value) = (key.trim(), value.trim());
+            let color = || parse_color(value).map_err(invalid_data);
+            match key {
+                "background" => theme.background = color()?,
+                "grid_line" => theme.grid_line = color()?,
+                "closed" => theme.closed = color()?,
+                "highlighted" => theme.highlighted = color()?,
+                "opened" => theme.opened = color()?,
+                "marked" => theme.marked = color()?,
+                "mine" => theme.mine = color()?,
+                "blown_mine" => theme.blown_mine = color()?,
+                "cursor" => theme.cursor = color()?,
+                "question" => theme.question = color()?,
+                "sprites" => sprites = Some(dir.join(value)),
+                "sprite_size" => {
+                    sprite_size = Some(
+                        value
+                            .parse::<f32>()
+                            .map_err(|_| invalid_data(format!("bad sprite size: {}", value)))?,
+                    )
+                }
+                "font" => {
+                    let font = load_ttf_font_from_bytes(&fs::read(dir.join(value))?)
+                        .map_err(|e| invalid_data(format!("bad font {}: {}", value, e)))?;
+                    theme.font = Some(font);
+                }
+                _ => match key.strip_prefix("number_").map(str::parse::<usize>) {
+                    Some(Ok(x @ 1..=8)) => theme.numbers[x - 1] = color()?,
+                    _ => return Err(invalid_data(format!("unknown theme key: {}", key))),
+                },
+            }
+        }
+
+        if let Some(sprites) = sprites {
+            let texture = Texture2D::from_file_with_format(&fs::read(sprites)?, None);
+            texture.set_filter(FilterMode::Nearest);
+            theme.sprites = Some(SpriteSheet {
+                tile_size: sprite_size.unwrap_or(texture.height()),
+                texture,
+            });
+        }
+        Ok(theme)
+    }
+
+    pub fn with_accessibility(&self, accessibility: &Accessibility) -> Theme {
+        let mut theme = self.clone();
+        match accessibility.palette {
+            Palette::Theme => {}
+            Palette::ColorblindSafe => {
+                theme.closed = Color::from_hex(0x5a5a5a);
+                theme.highlighted = Color::from_hex(0x8c8c8c);
+                theme.opened = WHITE;
+                theme.marked = Color::from_hex(0xe69f00);
+                theme.mine = Color::from_hex(0x000000);
+                theme.blown_mine = Color::from_hex(0xd55e00);
+                theme.cursor = Color::from_hex(0x0072b2);
+                theme.question = WHITE;
+                theme.numbers = hex_colors([
+                    0x0072b2, 0x009e73, 0xd55e00, 0xcc79a7, 0xe69f00, 0x56b4e9, 0x000000, 0x777777,
+                ]);
+                theme.glyph_color = BLACK;
+            }
+            Palette::HighContrast => {
+                theme.background = BLACK;
+                theme.grid_line = Color::from_hex(0x808080);
+                theme.closed = WHITE;
+                theme.highlighted = Color::from_hex(0xb0b0b0);
+                theme.opened = BLACK;
+                theme.marked = Color::from_hex(0xffff00);
+                theme.mine = Color::from_hex(0xff00ff);
+                theme.blown_mine = Color::from_hex(0xff0000);
+                theme.cursor = Color::from_hex(0xff00ff);
+                theme.question = BLACK;
+                theme.numbers = hex_colors([
+                    0x00ffff, 0x00ff00, 0xffff00, 0xff00ff, 0xff8000, 0xffffff, 0xffffff, 0xffffff,
+                ]);
+                theme.glyph_color = BLACK;
+                // sprites come with their own colors
+                theme.sprites = None;
+                theme.glyphs = true;
+            }
+        }
+        theme.glyphs |= accessibility.glyphs;
+        if accessibility.large_text {
+            theme.text_scale = LARGE_TEXT_SCALE;
+        }
+        theme
+    }
+
+    // counts below zero take the color of their size
+    pub fn number(&self, x: i8) -> Color {
+        self.numbers[(x.unsigned_abs() as usize).clamp(1, 8) - 1]
+    }
+}